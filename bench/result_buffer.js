@@ -0,0 +1,62 @@
+// Compares returning results as a fresh `Uint8Array` copy with writing them
+// into a reused `DiffBuffer` and reading them through a view.
+//
+// Run with ./scripts/bench.sh, which builds the nodejs package first.
+const { performance } = require("perf_hooks");
+const {
+  DiffBuffer,
+  DiffOptions,
+  DiffSession,
+  line_diff_with_options,
+} = require("../pkg-node/line_diff_wasm.js");
+
+const ITERATIONS = 2000;
+
+// a large document with a change every 10 lines, so results are big
+const baseline = Array.from({ length: 20000 }, (_, i) => `line ${i}`).join("\n");
+const text = baseline
+  .split("\n")
+  .map((line, i) => (i % 10 === 0 ? `${line} changed` : line))
+  .join("\n");
+
+function bench(name, run) {
+  let checksum = 0;
+  for (let i = 0; i < 50; i++) checksum += run(i);
+  const start = performance.now();
+  for (let i = 0; i < ITERATIONS; i++) checksum += run(i);
+  const perCall = ((performance.now() - start) * 1000) / ITERATIONS;
+  console.log(`${name.padEnd(40)} ${perCall.toFixed(1)} µs/call (${checksum})`);
+}
+
+// typing and deleting a character on line 2, like an editor session
+function keystroke(i) {
+  return i % 2 === 0 ? [2, 0, 2, 0, "x"] : [2, 0, 2, 1, ""];
+}
+
+const session = new DiffSession(baseline, text);
+bench("DiffSession.apply_change (copy)", (i) => {
+  const bytes = session.apply_change(...keystroke(i));
+  return bytes[bytes.length - 1];
+});
+
+const buffer = new DiffBuffer();
+bench("DiffSession.apply_change_into (view)", (i) => {
+  session.apply_change_into(...keystroke(i), buffer);
+  const bytes = buffer.view();
+  return bytes[bytes.length - 1];
+});
+session.free();
+
+const options = new DiffOptions();
+const small = text.split("\n").slice(0, 500).join("\n");
+const smallBaseline = baseline.split("\n").slice(0, 500).join("\n");
+bench("line_diff_with_options (copy)", () => {
+  const bytes = line_diff_with_options(smallBaseline, small, options);
+  return bytes[bytes.length - 1];
+});
+bench("DiffBuffer.line_diff (view)", () => {
+  buffer.line_diff(smallBaseline, small, options);
+  const bytes = buffer.view();
+  return bytes[bytes.length - 1];
+});
+buffer.free();
@@ -0,0 +1,74 @@
+// Diffs large synthetic files with `line_diff_with_options` and, for
+// comparison, with similar directly on the lines, which is what the diff
+// front end did before lines were interned and the common prefix and suffix
+// cut off.
+//
+// Run with `cargo bench --bench large_files`.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use line_diff_wasm::{line_diff_with_options, DiffAlgorithm, DiffOptions};
+use similar::{capture_diff_slices, Algorithm, DiffableStr};
+
+// Source-like lines with plenty of repeats, like braces and blank lines.
+fn file(lines: usize) -> String {
+    (0..lines)
+        .map(|i| match i % 7 {
+            0 => "}\n".to_string(),
+            1 => "\n".to_string(),
+            _ => format!("    let value_{} = compute({}, {});\n", i, i % 13, i / 7),
+        })
+        .collect()
+}
+
+// A few scattered edits, like a typical change to a large file.
+fn edited(old: &str, every: usize) -> String {
+    old.lines()
+        .enumerate()
+        .filter(|(i, _)| i % every != every / 2 + 1)
+        .map(|(i, line)| {
+            if i % every == every / 2 {
+                format!("{} // changed\n", line)
+            } else {
+                format!("{}\n", line)
+            }
+        })
+        .collect()
+}
+
+fn large_files(c: &mut Criterion) {
+    let old = file(100_000);
+    let cases = [
+        ("one_edit", edited(&old, 100_000)),
+        ("ten_edits", edited(&old, 10_000)),
+        ("hundred_edits", edited(&old, 1_000)),
+    ];
+    let algorithms = [
+        (DiffAlgorithm::Myers, Algorithm::Myers),
+        (DiffAlgorithm::Patience, Algorithm::Patience),
+    ];
+
+    let mut group = c.benchmark_group("100k_lines");
+    group.sample_size(10);
+    for (name, new) in &cases {
+        for &(algorithm, similar_algorithm) in &algorithms {
+            let options = DiffOptions {
+                algorithm,
+                ..DiffOptions::default()
+            };
+            let id = format!("{}/{:?}", name, algorithm);
+            group.bench_with_input(BenchmarkId::new("line_diff", &id), new, |b, new| {
+                b.iter(|| line_diff_with_options(&old, new, &options))
+            });
+            group.bench_with_input(BenchmarkId::new("similar_lines", &id), new, |b, new| {
+                b.iter(|| {
+                    let old_lines = old.tokenize_lines();
+                    let new_lines = new.tokenize_lines();
+                    capture_diff_slices(similar_algorithm, &old_lines, &new_lines)
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, large_files);
+criterion_main!(benches);
@@ -0,0 +1,73 @@
+// The worker side of `DiffWorkerClient`, bundle it as a module worker.
+import { default as wasmbin } from "line-diff-wasm/line_diff_wasm_bg.wasm";
+import init, {
+  CancellationToken,
+  DiffOptions,
+  line_diff_cancellable,
+} from "line-diff-wasm";
+import type { DiffRequest, DiffResponse, WorkerDiffOptions } from "./diff_worker_client";
+
+type Diff = Extract<DiffRequest, { type: "diff" }>;
+
+const ready = init(wasmbin);
+const queue: Diff[] = [];
+let scheduled = false;
+
+self.onmessage = (event: MessageEvent<DiffRequest>) => {
+  const request = event.data;
+  if (request.type === "cancel") {
+    // the client no longer waits for it, so it is just dropped
+    const queued = queue.findIndex((diff) => diff.id === request.id);
+    if (queued >= 0) {
+      queue.splice(queued, 1);
+    }
+    return;
+  }
+  queue.push(request);
+  schedule();
+};
+
+// Diffs run from a task of their own, so cancel messages that arrived in the
+// meantime are seen first.
+function schedule(): void {
+  if (!scheduled && queue.length > 0) {
+    scheduled = true;
+    setTimeout(runNext, 0);
+  }
+}
+
+async function runNext(): Promise<void> {
+  await ready;
+  scheduled = false;
+  const request = queue.shift();
+  if (request) {
+    reply(run(request));
+  }
+  schedule();
+}
+
+function run(request: Diff): DiffResponse {
+  const { id, oldText, newText, flag } = request;
+  const options = toDiffOptions(request.options);
+  const token = new CancellationToken(flag);
+  try {
+    return { id, result: line_diff_cancellable(oldText, newText, options, token) ?? null };
+  } catch (error) {
+    return { id, error: String(error) };
+  } finally {
+    token.free();
+    options.free();
+  }
+}
+
+function toDiffOptions(fields: WorkerDiffOptions): DiffOptions {
+  const options = new DiffOptions();
+  Object.assign(options, fields);
+  return options;
+}
+
+// The result is transferred rather than copied to the main thread.
+function reply(response: DiffResponse): void {
+  const transfer = "result" in response && response.result ? [response.result.buffer] : [];
+  (self as unknown as Worker).postMessage(response, transfer);
+}
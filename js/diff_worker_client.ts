@@ -0,0 +1,117 @@
+import type { DiffAlgorithm, DiffResult } from "line-diff-wasm";
+import { decodeLineDiff } from "./line_diff_format";
+
+/** The `DiffOptions` fields, as a plain object that can be posted to a worker. */
+export interface WorkerDiffOptions {
+  algorithm?: DiffAlgorithm;
+  ignore_line_endings?: boolean;
+  ignore_trailing_whitespace?: boolean;
+  ignore_whitespace?: boolean;
+  ignore_blank_lines?: boolean;
+  compact_encoding?: boolean;
+  timeout_ms?: number;
+  detect_moves?: boolean;
+  similarity_threshold?: number;
+}
+
+export type DiffRequest =
+  | {
+      type: "diff";
+      id: number;
+      oldText: string;
+      newText: string;
+      options: WorkerDiffOptions;
+      flag: Int32Array;
+    }
+  | { type: "cancel"; id: number };
+
+/** `result` is the `line_diff` result, or null when the diff was cancelled. */
+export type DiffResponse =
+  | { id: number; result: Uint8Array | null }
+  | { id: number; error: string };
+
+interface Pending {
+  key: string;
+  flag: Int32Array;
+  resolve(result: DiffResult | null): void;
+  reject(error: Error): void;
+}
+
+/**
+ * Runs diffs in a worker started from `diff_worker.ts`, so large files do not
+ * block the main thread.
+ *
+ * A new diff cancels the unfinished ones with the same key, which then
+ * resolve to null. Diffs that already started only stop early when the page
+ * is cross-origin isolated, since that needs a `SharedArrayBuffer`; otherwise
+ * they are only skipped if the worker has not started them yet.
+ */
+export class DiffWorkerClient {
+  private nextId = 1;
+  private pending = new Map<number, Pending>();
+
+  constructor(private worker: Worker) {
+    worker.onmessage = (event: MessageEvent<DiffResponse>) => {
+      const response = event.data;
+      const pending = this.pending.get(response.id);
+      if (!pending) {
+        return; // cancelled
+      }
+      this.pending.delete(response.id);
+      if ("error" in response) {
+        pending.reject(new Error(response.error));
+      } else if (response.result === null) {
+        pending.resolve(null);
+      } else {
+        try {
+          pending.resolve(decodeLineDiff(response.result));
+        } catch (error) {
+          pending.reject(error as Error);
+        }
+      }
+    };
+  }
+
+  diff(
+    oldText: string,
+    newText: string,
+    options: WorkerDiffOptions = {},
+    key = "",
+  ): Promise<DiffResult | null> {
+    for (const [id, pending] of this.pending) {
+      if (pending.key === key) {
+        this.cancelRequest(id, pending);
+      }
+    }
+    const id = this.nextId++;
+    const flag =
+      typeof SharedArrayBuffer !== "undefined" && self.crossOriginIsolated
+        ? new Int32Array(new SharedArrayBuffer(4))
+        : new Int32Array(1);
+    return new Promise((resolve, reject) => {
+      this.pending.set(id, { key, flag, resolve, reject });
+      const request: DiffRequest = { type: "diff", id, oldText, newText, options, flag };
+      this.worker.postMessage(request);
+    });
+  }
+
+  /** Cancels all unfinished diffs, which resolve to null. */
+  cancel(): void {
+    for (const [id, pending] of this.pending) {
+      this.cancelRequest(id, pending);
+    }
+  }
+
+  terminate(): void {
+    this.cancel();
+    this.worker.terminate();
+  }
+
+  private cancelRequest(id: number, pending: Pending): void {
+    Atomics.store(pending.flag, 0, 1);
+    const request: DiffRequest = { type: "cancel", id };
+    this.worker.postMessage(request);
+    this.pending.delete(id);
+    pending.resolve(null);
+  }
+}
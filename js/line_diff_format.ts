@@ -0,0 +1,104 @@
+// Generated from src/format.rs by `UPDATE_TS_DECODER=1 cargo test`, do not edit.
+
+import { DiffKind, DiffResult, LineDiff } from "line-diff-wasm";
+
+const MAGIC = [0x4c, 0x44, 0x49, 0x46]; // "LDIF"
+const VERSION = 1;
+const HEADER_LENGTH = 10;
+const FLAG_VARINT = 1;
+const FLAG_BINARY = 2;
+const FLAG_DIFFERS = 4;
+const FLAG_APPROXIMATE = 8;
+
+/**
+ * Reads the markers from a `line_diff` result in either encoding, or whether
+ * binary content differs.
+ */
+export function decodeLineDiff(bytes: Uint8Array): DiffResult {
+  if (bytes.length < MAGIC.length || MAGIC.some((b, i) => bytes[i] !== b)) {
+    throw new Error("not a line-diff result");
+  }
+  if (bytes.length < HEADER_LENGTH) {
+    throw new Error("truncated line-diff result");
+  }
+  const view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+  const version = bytes[4];
+  if (version !== VERSION) {
+    throw new Error(`unsupported line-diff format version ${version}`);
+  }
+  const count = view.getUint32(5, false);
+  const flags = bytes[9];
+  const binary = (flags & FLAG_BINARY) !== 0;
+  if (
+    (flags & ~(FLAG_VARINT | FLAG_BINARY | FLAG_DIFFERS | FLAG_APPROXIMATE)) !== 0 ||
+    ((flags & FLAG_DIFFERS) !== 0 && !binary) ||
+    ((flags & FLAG_APPROXIMATE) !== 0 && binary)
+  ) {
+    throw new Error(`unsupported line-diff format flags ${flags}`);
+  }
+  if (binary) {
+    if (count !== 0) {
+      throw new Error("binary line-diff result with records");
+    }
+    if (bytes.length > HEADER_LENGTH) {
+      throw new Error("trailing bytes after the last record");
+    }
+    return { binary: true, identical: (flags & FLAG_DIFFERS) === 0 };
+  }
+  const varint = (flags & FLAG_VARINT) !== 0;
+
+  let offset = HEADER_LENGTH;
+  const byte = (): number => {
+    if (offset >= bytes.length) {
+      throw new Error("truncated line-diff result");
+    }
+    return bytes[offset++];
+  };
+  const u32 = (): number => {
+    if (!varint) {
+      if (offset + 4 > bytes.length) {
+        throw new Error("truncated line-diff result");
+      }
+      const value = view.getUint32(offset, false);
+      offset += 4;
+      return value;
+    }
+    let value = 0;
+    for (let shift = 0; shift < 35; shift += 7) {
+      const b = byte();
+      value += (b & 0x7f) * 2 ** shift;
+      if ((b & 0x80) === 0) {
+        if (value > 0xffffffff) break;
+        return value;
+      }
+    }
+    throw new Error("invalid varint in line-diff result");
+  };
+  const kind = (): DiffKind => {
+    const k = byte();
+    if (k < DiffKind.Add || k > DiffKind.MovedTo) {
+      throw new Error(`invalid marker kind ${k}`);
+    }
+    return k;
+  };
+
+  const markers: LineDiff[] = [];
+  for (let i = 0; i < count; i++) {
+    markers.push({
+      startLine: u32(),
+      endLine: u32(),
+      kind: kind(),
+      oldStartLine: u32(),
+      oldEndLine: u32(),
+      deletedLineCount: u32(),
+    });
+  }
+  if (offset !== bytes.length) {
+    throw new Error("trailing bytes after the last record");
+  }
+  return {
+    binary: false,
+    markers,
+    approximate: (flags & FLAG_APPROXIMATE) !== 0,
+  };
+}
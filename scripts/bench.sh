@@ -0,0 +1,7 @@
+#!/bin/bash
+set -ex
+
+cd "${0%/*}/.."
+
+wasm-pack build --release --target nodejs --out-dir pkg-node
+node bench/result_buffer.js
@@ -0,0 +1,303 @@
+use similar::{Algorithm, ChangeTag, TextDiff};
+
+use crate::clock::{exceeded, Instant};
+
+// Runs with more deleted times inserted lines than this are not searched for
+// the best pairs, comparing every pair would take too long. Each deleted line
+// is paired with the most similar of the next `WINDOW` inserted lines
+// instead.
+const MAX_PAIRS: usize = 1000;
+const WINDOW: usize = 16;
+
+// How similar two lines are, from 0 to 1, ignoring their line endings.
+// `None` once the deadline passed, even in the middle of comparing two long
+// lines.
+fn similarity(old_line: &[u8], new_line: &[u8], deadline: Option<Instant>) -> Option<f32> {
+    fn trim(line: &[u8]) -> &[u8] {
+        let end = line.iter().rposition(|&b| b != b'\r' && b != b'\n');
+        &line[..end.map_or(0, |end| end + 1)]
+    }
+    if exceeded(deadline) {
+        return None;
+    }
+    let mut config = TextDiff::configure();
+    config.algorithm(Algorithm::Myers);
+    if let Some(deadline) = deadline {
+        config.deadline(deadline);
+    }
+    let ratio = config.diff_chars(trim(old_line), trim(new_line)).ratio();
+    // past the deadline the ratio is of a coarser diff and too low
+    Some(ratio).filter(|_| !exceeded(deadline))
+}
+
+// `collect_diffs` pairs inserted lines with the pending deleted lines in
+// order. Within each run of changes this reorders the tags so that it pairs
+// lines that are at least `threshold` similar instead, picking the pairs with
+// the highest total similarity. Returns the tag indices in front of which the
+// pending deletes have to become a delete caret, because they are not paired
+// with the inserts that follow.
+//
+// Deleted and inserted lines each stay in their order, so line numbers do
+// not change. Ignored lines are never paired, and neither are lines compared
+// after the deadline.
+pub(crate) fn align_by_similarity<'a>(
+    tags: &mut [(ChangeTag, bool)],
+    threshold: f32,
+    deadline: Option<Instant>,
+    old_line: impl Fn(usize) -> &'a [u8],
+    new_line: impl Fn(usize) -> &'a [u8],
+) -> Vec<usize> {
+    let mut breaks = Vec::new();
+    let (mut old_index, mut new_index) = (0, 0);
+    let mut start = 0;
+    while start < tags.len() {
+        if tags[start].0 == ChangeTag::Equal {
+            old_index += 1;
+            new_index += 1;
+            start += 1;
+            continue;
+        }
+        let len = tags[start..]
+            .iter()
+            .take_while(|(tag, _)| *tag != ChangeTag::Equal)
+            .count();
+        let run = &mut tags[start..start + len];
+        let deletes = run.iter().filter(|(tag, _)| *tag == ChangeTag::Delete);
+        let delete_count = deletes.count();
+        let insert_count = len - delete_count;
+        align_run(
+            run,
+            start,
+            threshold,
+            deadline,
+            |i| old_line(old_index + i),
+            |j| new_line(new_index + j),
+            &mut breaks,
+        );
+        old_index += delete_count;
+        new_index += insert_count;
+        start += len;
+    }
+    breaks
+}
+
+fn align_run<'a>(
+    run: &mut [(ChangeTag, bool)],
+    offset: usize,
+    threshold: f32,
+    deadline: Option<Instant>,
+    old_line: impl Fn(usize) -> &'a [u8],
+    new_line: impl Fn(usize) -> &'a [u8],
+    breaks: &mut Vec<usize>,
+) {
+    let lines = |change: ChangeTag| -> Vec<bool> {
+        run.iter()
+            .filter(|(tag, _)| *tag == change)
+            .map(|&(_, ignored)| ignored)
+            .collect()
+    };
+    // whether each deleted and inserted line is ignored
+    let deleted = lines(ChangeTag::Delete);
+    let inserted = lines(ChangeTag::Insert);
+    let old: Vec<usize> = (0..deleted.len()).filter(|&i| !deleted[i]).collect();
+    let new: Vec<usize> = (0..inserted.len()).filter(|&j| !inserted[j]).collect();
+    if old.is_empty() || new.is_empty() {
+        return;
+    }
+    let similar = |i: usize, j: usize| {
+        let ratio = similarity(old_line(old[i]), new_line(new[j]), deadline)?;
+        Some(ratio).filter(|&ratio| ratio >= threshold)
+    };
+    let pairs: Vec<(usize, usize)> = if old.len() * new.len() > MAX_PAIRS {
+        window_pairs(old.len(), new.len(), similar)
+    } else {
+        best_pairs(old.len(), new.len(), similar)
+    };
+    let pairs = pairs.into_iter().map(|(i, j)| (old[i], new[j]));
+
+    // between pairs, the deletes go first and become a caret in front of the
+    // inserts
+    let mut aligned = Vec::with_capacity(run.len());
+    let (mut next_delete, mut next_insert) = (0, 0);
+    let end = (deleted.len(), inserted.len());
+    for pair in pairs.map(Some).chain(std::iter::once(None)) {
+        let (to_delete, to_insert) = pair.unwrap_or(end);
+        if to_delete > next_delete {
+            aligned.extend((next_delete..to_delete).map(|i| (ChangeTag::Delete, deleted[i])));
+            breaks.push(offset + aligned.len());
+        }
+        aligned.extend((next_insert..to_insert).map(|j| (ChangeTag::Insert, inserted[j])));
+        if let Some((d, i)) = pair {
+            aligned.push((ChangeTag::Delete, false));
+            aligned.push((ChangeTag::Insert, false));
+            next_delete = d + 1;
+            next_insert = i + 1;
+        }
+    }
+    run.copy_from_slice(&aligned);
+}
+
+// The pairs, in order, with the highest total similarity among `old_len`
+// deleted and `new_len` inserted lines.
+fn best_pairs(
+    old_len: usize,
+    new_len: usize,
+    similar: impl Fn(usize, usize) -> Option<f32>,
+) -> Vec<(usize, usize)> {
+    // the best total similarity of pairs among the first i deleted and the
+    // first j inserted lines
+    let mut ratios = vec![vec![None; new_len]; old_len];
+    let mut best = vec![vec![0.0f32; new_len + 1]; old_len + 1];
+    for i in 1..=old_len {
+        for j in 1..=new_len {
+            ratios[i - 1][j - 1] = similar(i - 1, j - 1);
+            let paired = ratios[i - 1][j - 1].map_or(0.0, |ratio| best[i - 1][j - 1] + ratio);
+            best[i][j] = paired.max(best[i - 1][j]).max(best[i][j - 1]);
+        }
+    }
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (old_len, new_len);
+    while i > 0 && j > 0 {
+        match ratios[i - 1][j - 1] {
+            Some(ratio) if best[i][j] == best[i - 1][j - 1] + ratio => {
+                pairs.push((i - 1, j - 1));
+                i -= 1;
+                j -= 1;
+            }
+            _ if best[i][j] == best[i - 1][j] => i -= 1,
+            _ => j -= 1,
+        }
+    }
+    pairs.reverse();
+    pairs
+}
+
+// Pairs each deleted line with the most similar of the next `WINDOW` inserted
+// lines after the last pair, if any is similar enough.
+fn window_pairs(
+    old_len: usize,
+    new_len: usize,
+    similar: impl Fn(usize, usize) -> Option<f32>,
+) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    let mut next = 0;
+    for i in 0..old_len {
+        let best = (next..new_len.min(next + WINDOW))
+            .filter_map(|j| similar(i, j).map(|ratio| (j, ratio)))
+            .fold(None, |best: Option<(usize, f32)>, (j, ratio)| match best {
+                Some((_, best_ratio)) if best_ratio >= ratio => best,
+                _ => Some((j, ratio)),
+            });
+        if let Some((j, _)) = best {
+            pairs.push((i, j));
+            next = j + 1;
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use similar::ChangeTag::{Delete, Insert};
+
+    use crate::align::{align_by_similarity, similarity, MAX_PAIRS};
+    use crate::clock::Instant;
+
+    #[test]
+    fn similar_lines() {
+        assert_eq!(similarity(b"abcd\n", b"bcde\r\n", None), Some(0.75));
+        assert_eq!(similarity(b"\n", b"", None), Some(1.0));
+        assert_eq!(similarity(b"abc", b"xyz", None), Some(0.0));
+    }
+
+    #[test]
+    fn pairs_most_similar_lines() {
+        let old = ["let a = 1;\n", "unrelated\n"];
+        let new = ["let z = 0;\n", "let a = 2;\n"];
+        let mut tags = vec![
+            (Delete, false),
+            (Delete, false),
+            (Insert, false),
+            (Insert, false),
+        ];
+        let breaks = align_by_similarity(
+            &mut tags,
+            0.5,
+            None,
+            |i| old[i].as_bytes(),
+            |j| new[j].as_bytes(),
+        );
+        // `let z` is added, `let a` modified and `unrelated` deleted after it
+        assert_eq!(
+            tags,
+            vec![
+                (Insert, false),
+                (Delete, false),
+                (Insert, false),
+                (Delete, false)
+            ]
+        );
+        assert_eq!(breaks, vec![4]);
+    }
+
+    #[test]
+    fn large_runs_still_use_the_threshold() {
+        let mut old: Vec<String> = (0..40).map(|i| format!("{:08}\n", i)).collect();
+        let mut new = vec!["########\n".to_string(); 40];
+        old[3] = "let value = 3;\n".to_string();
+        new[10] = "let value = 30;\n".to_string();
+        assert!(old.len() * new.len() > MAX_PAIRS);
+        let mut tags: Vec<_> = std::iter::repeat_n((Delete, false), 40)
+            .chain(std::iter::repeat_n((Insert, false), 40))
+            .collect();
+        let breaks = align_by_similarity(
+            &mut tags,
+            0.5,
+            None,
+            |i| old[i].as_bytes(),
+            |j| new[j].as_bytes(),
+        );
+        // only `value_3` is paired, the other lines are deletes and adds
+        let run = |tag, len| std::iter::repeat_n((tag, false), len);
+        let expected: Vec<_> = run(Delete, 3)
+            .chain(run(Insert, 10))
+            .chain([(Delete, false), (Insert, false)])
+            .chain(run(Delete, 36))
+            .chain(run(Insert, 29))
+            .collect();
+        assert_eq!(tags, expected);
+        assert_eq!(breaks, vec![3, 51]);
+    }
+
+    #[test]
+    fn nothing_is_paired_past_the_deadline() {
+        let old = ["let a = 1;\n"];
+        let new = ["let a = 2;\n"];
+        let mut tags = vec![(Delete, false), (Insert, false)];
+        let past = Instant::now();
+        while Instant::now() <= past {}
+        let breaks = align_by_similarity(
+            &mut tags,
+            0.5,
+            Some(past),
+            |i| old[i].as_bytes(),
+            |j| new[j].as_bytes(),
+        );
+        assert_eq!(tags, vec![(Delete, false), (Insert, false)]);
+        assert_eq!(breaks, vec![1]);
+    }
+
+    #[test]
+    fn long_lines_stop_at_the_deadline() {
+        // minified lines with next to nothing in common, which Myers takes
+        // minutes to compare
+        let old: String = (0..200_000).map(|i| ['a', 'b'][i % 2]).collect();
+        let new: String = (0..200_000).map(|i| ['c', 'a', 'd'][i % 3]).collect();
+        let started = Instant::now();
+        let deadline = started.checked_add(Duration::from_millis(50));
+        assert_eq!(similarity(old.as_bytes(), new.as_bytes(), deadline), None);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,289 @@
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+use std::process;
+
+use line_diff_wasm::{
+    decode_line_diff, line_diff_bytes_with_options, DiffAlgorithm, DiffOptions, HunkKind,
+    LineDiffResult,
+};
+
+const USAGE: &str = "\
+Usage: line-diff [OPTIONS] OLD NEW
+
+Prints the gutter markers for the change from OLD to NEW. Either file can be
+`-` to read it from stdin, e.g. `git show HEAD:src/main.rs | line-diff - src/main.rs`.
+
+Options:
+  -f, --format <FORMAT>         text (default), json or binary
+  -a, --algorithm <ALGORITHM>   patience (default), myers or lcs
+      --ignore-line-endings     CRLF vs LF
+      --ignore-trailing-whitespace
+  -w, --ignore-whitespace       like `git diff -w`
+      --ignore-blank-lines
+      --detect-moves            report moved blocks as moved-from and moved-to
+      --similarity <RATIO>      only pair lines at least this similar (0 to 1)
+                                into modifies
+      --compact                 varint records in the binary format
+      --timeout-ms <MS>
+  -h, --help
+
+Exits with 0 when there are no markers, 1 when there are and 2 on errors.";
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Format {
+    Text,
+    Json,
+    Binary,
+}
+
+#[derive(Debug, PartialEq)]
+struct Args {
+    old_path: String,
+    new_path: String,
+    format: Format,
+    options: DiffOptions,
+}
+
+fn main() {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(error) => {
+            eprintln!("line-diff: {}\n\n{}", error, USAGE);
+            process::exit(2);
+        }
+    };
+    match run(&args) {
+        Ok(differs) => process::exit(differs as i32),
+        Err(error) => {
+            eprintln!("line-diff: {}", error);
+            process::exit(2);
+        }
+    }
+}
+
+// `None` when help was asked for.
+fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Option<Args>, String> {
+    let mut args = args.into_iter();
+    let mut paths = Vec::new();
+    let mut format = Format::Text;
+    let mut options = DiffOptions::default();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-f" | "--format" => {
+                format = match value(&arg)?.as_str() {
+                    "text" => Format::Text,
+                    "json" => Format::Json,
+                    "binary" => Format::Binary,
+                    other => return Err(format!("unknown format `{}`", other)),
+                }
+            }
+            "-a" | "--algorithm" => {
+                options.algorithm = match value(&arg)?.as_str() {
+                    "patience" => DiffAlgorithm::Patience,
+                    "myers" => DiffAlgorithm::Myers,
+                    "lcs" => DiffAlgorithm::Lcs,
+                    other => return Err(format!("unknown algorithm `{}`", other)),
+                }
+            }
+            "--ignore-line-endings" => options.ignore_line_endings = true,
+            "--ignore-trailing-whitespace" => options.ignore_trailing_whitespace = true,
+            "-w" | "--ignore-whitespace" => options.ignore_whitespace = true,
+            "--ignore-blank-lines" => options.ignore_blank_lines = true,
+            "--detect-moves" => options.detect_moves = true,
+            "--compact" => options.compact_encoding = true,
+            "--similarity" => {
+                let ratio = value(&arg)?;
+                options.similarity_threshold = ratio
+                    .parse()
+                    .ok()
+                    .filter(|ratio| (0.0..=1.0).contains(ratio))
+                    .ok_or_else(|| format!("invalid similarity `{}`", ratio))?;
+            }
+            "--timeout-ms" => {
+                let ms = value(&arg)?;
+                options.timeout_ms = ms
+                    .parse()
+                    .map_err(|_| format!("invalid timeout `{}`", ms))?;
+            }
+            _ if arg.starts_with('-') && arg != "-" => {
+                return Err(format!("unknown option `{}`", arg))
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() != 2 {
+        return Err("expected two files".to_string());
+    }
+    if paths[0] == "-" && paths[1] == "-" {
+        return Err("only one file can be read from stdin".to_string());
+    }
+    let new_path = paths.pop().unwrap();
+    let old_path = paths.pop().unwrap();
+    Ok(Some(Args {
+        old_path,
+        new_path,
+        format,
+        options,
+    }))
+}
+
+// Whether there are differences.
+fn run(args: &Args) -> Result<bool, String> {
+    let old = read(&args.old_path)?;
+    let new = read(&args.new_path)?;
+    // the same entry point and format as the wasm build, decoded again for
+    // the text and JSON output
+    let encoded = line_diff_bytes_with_options(&old, &new, &args.options);
+    let result = decode_line_diff(&encoded).map_err(|error| error.to_string())?;
+    let mut stdout = io::stdout().lock();
+    let written = match args.format {
+        Format::Text => stdout.write_all(render_text(&result, args).as_bytes()),
+        Format::Json => serde_json::to_writer(&mut stdout, &result)
+            .map_err(io::Error::from)
+            .and_then(|()| stdout.write_all(b"\n")),
+        Format::Binary => stdout.write_all(&encoded),
+    };
+    written
+        .and_then(|()| stdout.flush())
+        .map_err(|error| format!("cannot write output: {}", error))?;
+    Ok(differs(&result))
+}
+
+fn read(path: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let read = if path == "-" {
+        io::stdin().read_to_end(&mut bytes).map(|_| ())
+    } else {
+        std::fs::read(path).map(|read| bytes = read)
+    };
+    read.map_err(|error| format!("cannot read {}: {}", path, error))?;
+    Ok(bytes)
+}
+
+fn differs(result: &LineDiffResult) -> bool {
+    match result {
+        LineDiffResult::Text { markers, .. } => !markers.is_empty(),
+        LineDiffResult::Binary { identical } => !identical,
+    }
+}
+
+// One marker per line, e.g. `modify 3-4 old 3-5 deleted 3`, lines are 1-based
+// and inclusive. A `moved-to` has the old lines of its `moved-from`.
+fn render_text(result: &LineDiffResult, args: &Args) -> String {
+    let mut out = String::new();
+    match result {
+        LineDiffResult::Text {
+            markers,
+            approximate,
+        } => {
+            for marker in markers {
+                let kind = match marker.kind {
+                    HunkKind::Add => "add",
+                    HunkKind::Delete => "delete",
+                    HunkKind::Modify => "modify",
+                    HunkKind::Conflict => "conflict",
+                    HunkKind::MovedFrom => "moved-from",
+                    HunkKind::MovedTo => "moved-to",
+                };
+                let _ = write!(
+                    out,
+                    "{} {}-{} old {}-{}",
+                    kind,
+                    marker.start_line,
+                    marker.end_line,
+                    marker.old_start_line,
+                    marker.old_end_line
+                );
+                if marker.deleted_line_count > 0 {
+                    let _ = write!(out, " deleted {}", marker.deleted_line_count);
+                }
+                out.push('\n');
+            }
+            if *approximate {
+                out.push_str("# timed out, markers are approximate\n");
+            }
+        }
+        LineDiffResult::Binary { identical: false } => {
+            let _ = writeln!(
+                out,
+                "Binary files {} and {} differ",
+                args.old_path, args.new_path
+            );
+        }
+        LineDiffResult::Binary { identical: true } => {}
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use line_diff_wasm::{decode_line_diff, line_diff_bytes_with_options, DiffAlgorithm};
+
+    use crate::{differs, parse_args, render_text, Args, Format};
+
+    fn args(list: &[&str]) -> Result<Option<Args>, String> {
+        parse_args(list.iter().map(|arg| arg.to_string()))
+    }
+
+    fn text_output(old: &str, new: &str, args: &Args) -> (String, bool) {
+        let encoded = line_diff_bytes_with_options(old.as_bytes(), new.as_bytes(), &args.options);
+        let result = decode_line_diff(&encoded).unwrap();
+        (render_text(&result, args), differs(&result))
+    }
+
+    #[test]
+    fn parses_options() {
+        let parsed = args(&["-w", "--format", "json", "-a", "myers", "-", "b.txt"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(parsed.old_path, "-");
+        assert_eq!(parsed.new_path, "b.txt");
+        assert_eq!(parsed.format, Format::Json);
+        assert_eq!(parsed.options.algorithm, DiffAlgorithm::Myers);
+        assert!(parsed.options.ignore_whitespace);
+        assert!(!parsed.options.ignore_blank_lines);
+
+        assert_eq!(args(&["a", "--help"]), Ok(None));
+        assert!(args(&["a"]).is_err());
+        assert!(args(&["-", "-"]).is_err());
+        assert!(args(&["--format", "xml", "a", "b"]).is_err());
+        assert!(args(&["--timeout-ms"]).is_err());
+        assert!(args(&["--similarity", "2", "a", "b"]).is_err());
+        assert!(args(&["--frobnicate", "a", "b"]).is_err());
+    }
+
+    #[test]
+    fn prints_markers() {
+        let parsed = args(&["old.txt", "new.txt"]).unwrap().unwrap();
+        assert_eq!(
+            text_output("a\nb\nc\nd\n", "a\nB\nc\nnew\nd\n", &parsed),
+            (
+                "modify 2-2 old 2-2 deleted 1\nadd 4-4 old 4-4\n".to_string(),
+                true
+            )
+        );
+        assert_eq!(text_output("a\n", "a\n", &parsed), (String::new(), false));
+        assert_eq!(
+            text_output("a\0", "b\0", &parsed),
+            (
+                "Binary files old.txt and new.txt differ\n".to_string(),
+                true
+            )
+        );
+    }
+
+    #[test]
+    fn ignored_changes_do_not_differ() {
+        let parsed = args(&["-w", "a", "b"]).unwrap().unwrap();
+        assert_eq!(
+            text_output("a b\n", "a  b\n", &parsed),
+            (String::new(), false)
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use crate::cancel::IsCancelled;
+use crate::clock::{deadline_after, exceeded};
+use crate::{diff_bytes_with_options, diff_with_deadline, DiffOptions, LineDiffResult};
+
+// Like git, only the start of a file is looked at.
+const SNIFF_LEN: usize = 8000;
+
+// Whether `bytes` look like an image, archive or other binary file rather
+// than text: a NUL byte, or more than 30% control characters that do not
+// show up in text files.
+pub(crate) fn is_binary(bytes: &[u8]) -> bool {
+    let sample = &bytes[..bytes.len().min(SNIFF_LEN)];
+    if sample.contains(&0) {
+        return true;
+    }
+    let control = sample
+        .iter()
+        .filter(|&&b| (b < 0x20 && !b"\t\n\r\x0c\x1b\x08".contains(&b)) || b == 0x7f)
+        .count();
+    control * 10 > sample.len() * 3
+}
+
+// With `cancelled`, the markers are meaningless once it returns true.
+pub(crate) fn diff_result(
+    old_text: &str,
+    new_text: &str,
+    options: &DiffOptions,
+    cancelled: Option<IsCancelled>,
+) -> LineDiffResult {
+    if is_binary(old_text.as_bytes()) || is_binary(new_text.as_bytes()) {
+        return LineDiffResult::Binary {
+            identical: old_text == new_text,
+        };
+    }
+    let deadline = deadline_after(options.timeout_ms);
+    LineDiffResult::Text {
+        markers: diff_with_deadline(old_text, new_text, options, deadline, cancelled),
+        approximate: exceeded(deadline),
+    }
+}
+
+pub(crate) fn diff_bytes_result(
+    old_bytes: &[u8],
+    new_bytes: &[u8],
+    options: &DiffOptions,
+) -> LineDiffResult {
+    if is_binary(old_bytes) || is_binary(new_bytes) {
+        return LineDiffResult::Binary {
+            identical: old_bytes == new_bytes,
+        };
+    }
+    let deadline = deadline_after(options.timeout_ms);
+    LineDiffResult::Text {
+        markers: diff_bytes_with_options(old_bytes, new_bytes, options, deadline),
+        approximate: exceeded(deadline),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::binary::{diff_bytes_result, diff_result, is_binary};
+    use crate::{diff, DiffOptions, LineDiffResult};
+
+    #[test]
+    fn detection() {
+        assert!(!is_binary(b""));
+        assert!(!is_binary(
+            "plain text\twith tabs\r\nand \u{1F600}\n".as_bytes()
+        ));
+        assert!(is_binary(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"));
+        assert!(is_binary(b"\x01\x02\x03abc"));
+        assert!(!is_binary(b"\x01abcdefghij"));
+        // only the start of the file counts
+        let mut late_nul = vec![b'a'; 9000];
+        late_nul.push(0);
+        assert!(!is_binary(&late_nul));
+    }
+
+    #[test]
+    fn binary_results() {
+        let options = DiffOptions::default();
+        assert_eq!(
+            diff_result("a\0b", "a\0c", &options, None),
+            LineDiffResult::Binary { identical: false }
+        );
+        assert_eq!(
+            diff_result("a\n", "a\0b", &options, None),
+            LineDiffResult::Binary { identical: false }
+        );
+        assert_eq!(
+            diff_bytes_result(b"\xff\0", b"\xff\0", &options),
+            LineDiffResult::Binary { identical: true }
+        );
+        assert_eq!(
+            serde_json::to_string(&LineDiffResult::Binary { identical: true }).unwrap(),
+            "{\"binary\":true,\"identical\":true}"
+        );
+        assert_eq!(
+            diff_result("a\n", "b\n", &options, None),
+            LineDiffResult::Text {
+                markers: diff("a\n", "b\n"),
+                approximate: false
+            }
+        );
+    }
+}
@@ -0,0 +1,97 @@
+#[cfg(feature = "wasm")]
+use js_sys::Uint8Array;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::binary::diff_result;
+use crate::format::write_result;
+use crate::DiffOptions;
+
+/// A result buffer in wasm memory that is reused for every diff, so results
+/// can be read from JS without allocating or copying them.
+///
+/// Each diff overwrites the previous result and keeps the allocation for the
+/// next one. `ptr` and `view` are only valid until the next diff into this
+/// buffer or until wasm memory grows, whichever comes first. Call `free()`
+/// to release the buffer.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Default)]
+pub struct DiffBuffer {
+    bytes: Vec<u8>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl DiffBuffer {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> DiffBuffer {
+        DiffBuffer::default()
+    }
+
+    /// Diffs like `line_diff_with_options` into the buffer and returns the
+    /// length of the result.
+    pub fn line_diff(&mut self, old_text: &str, new_text: &str, options: &DiffOptions) -> u32 {
+        let result = diff_result(old_text, new_text, options, None);
+        write_result(&mut self.bytes, &result, options.compact_encoding);
+        self.length()
+    }
+
+    /// Where the current result starts in wasm memory.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn ptr(&self) -> *const u8 {
+        self.bytes.as_ptr()
+    }
+
+    /// The length of the current result in bytes.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn length(&self) -> u32 {
+        self.bytes.len() as u32
+    }
+
+    /// A `Uint8Array` over the current result in wasm memory, without
+    /// copying it. Read it before the next diff.
+    #[cfg(feature = "wasm")]
+    pub fn view(&self) -> Uint8Array {
+        // Safety: the view is only valid until the buffer is written to or
+        // wasm memory grows, as documented above
+        unsafe { Uint8Array::view(&self.bytes) }
+    }
+}
+
+impl DiffBuffer {
+    pub(crate) fn bytes_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buffer::DiffBuffer;
+    use crate::{line_diff_with_options, DiffOptions};
+
+    #[test]
+    fn same_bytes_as_line_diff() {
+        let options = DiffOptions::default();
+        let mut buffer = DiffBuffer::new();
+        let length = buffer.line_diff("a\nb\nc\n", "a\nB\n", &options);
+        assert_eq!(length, buffer.length());
+        assert_eq!(
+            buffer.bytes,
+            line_diff_with_options("a\nb\nc\n", "a\nB\n", &options)
+        );
+    }
+
+    #[test]
+    fn reuses_the_allocation() {
+        let options = DiffOptions::default();
+        let mut buffer = DiffBuffer::new();
+        buffer.line_diff("a\nb\nc\n", "A\nb\nC\n", &options);
+        let ptr = buffer.ptr();
+        let length = buffer.line_diff("a\nb\nc\n", "a\nb\nC\n", &options);
+        assert_eq!(buffer.ptr(), ptr);
+        assert_eq!(
+            buffer.bytes,
+            line_diff_with_options("a\nb\nc\n", "a\nb\nC\n", &options)
+        );
+        assert_eq!(length, 31);
+    }
+}
@@ -0,0 +1,129 @@
+#[cfg(feature = "wasm")]
+use js_sys::{Atomics, Int32Array};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::binary::diff_result;
+#[cfg(feature = "wasm")]
+use crate::format::encode_result;
+use crate::{DiffOptions, LineDiffResult};
+
+// Asked while diffing whether the result is still wanted.
+pub(crate) type IsCancelled<'a> = &'a dyn Fn() -> bool;
+
+/// Lets the thread that asked for a diff stop it while it runs in a worker.
+///
+/// The diff is cancelled once the first element of `flag` is non-zero. To
+/// cancel a diff that already started, `flag` has to be a view of a
+/// `SharedArrayBuffer`, set with `Atomics.store(flag, 0, 1)` from the other
+/// thread. Over a plain `ArrayBuffer` it can only be set before the diff.
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub struct CancellationToken {
+    flag: Int32Array,
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+impl CancellationToken {
+    #[wasm_bindgen(constructor)]
+    pub fn new(flag: Int32Array) -> CancellationToken {
+        CancellationToken { flag }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        Atomics::load(&self.flag, 0).is_ok_and(|flag| flag != 0)
+    }
+}
+
+/// Like `line_diff_with_options`, but stops and returns `undefined` once
+/// `token` is cancelled.
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn line_diff_cancellable(
+    old_text: &str,
+    new_text: &str,
+    options: &DiffOptions,
+    token: &CancellationToken,
+) -> Option<Vec<u8>> {
+    let result = cancellable_result(old_text, new_text, options, &|| token.is_cancelled())?;
+    Some(encode_result(&result, options.compact_encoding))
+}
+
+#[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+fn cancellable_result(
+    old_text: &str,
+    new_text: &str,
+    options: &DiffOptions,
+    cancelled: IsCancelled,
+) -> Option<LineDiffResult> {
+    if cancelled() {
+        return None;
+    }
+    let result = diff_result(old_text, new_text, options, Some(cancelled));
+    if cancelled() {
+        return None;
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::binary::diff_result;
+    use crate::cancel::cancellable_result;
+    use crate::{DiffAlgorithm, DiffOptions};
+
+    fn edited_file() -> (String, String) {
+        let old: String = (0..2000).map(|i| format!("line {}\n", i)).collect();
+        let new: String = (0..2000)
+            .map(|i| {
+                if i % 10 == 0 {
+                    format!("changed {}\n", i)
+                } else {
+                    format!("line {}\n", i)
+                }
+            })
+            .collect();
+        (old, new)
+    }
+
+    #[test]
+    fn not_cancelled() {
+        let (old, new) = edited_file();
+        for &algorithm in &[DiffAlgorithm::Myers, DiffAlgorithm::Patience] {
+            let options = DiffOptions {
+                algorithm,
+                ..DiffOptions::default()
+            };
+            assert_eq!(
+                cancellable_result(&old, &new, &options, &|| false),
+                Some(diff_result(&old, &new, &options, None))
+            );
+        }
+    }
+
+    #[test]
+    fn stops_while_diffing() {
+        let (old, new) = edited_file();
+        for &algorithm in &[DiffAlgorithm::Myers, DiffAlgorithm::Patience] {
+            let options = DiffOptions {
+                algorithm,
+                ..DiffOptions::default()
+            };
+            let checks = Cell::new(0);
+            let cancelled = || {
+                checks.set(checks.get() + 1);
+                checks.get() > 5
+            };
+            assert_eq!(cancellable_result(&old, &new, &options, &cancelled), None);
+            // hundreds of ops if it had run to the end
+            assert_eq!(checks.get(), 7);
+        }
+        assert_eq!(
+            cancellable_result(&old, &new, &DiffOptions::default(), &|| true),
+            None
+        );
+    }
+}
@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+// similar measures deadlines with the same clock, which is
+// `performance.now()` in the browser and `std::time::Instant` natively.
+pub(crate) use web_time::Instant;
+
+// No deadline for a timeout of 0.
+pub(crate) fn deadline_after(timeout_ms: u32) -> Option<Instant> {
+    if timeout_ms == 0 {
+        return None;
+    }
+    Instant::now().checked_add(Duration::from_millis(timeout_ms.into()))
+}
+
+// Whether the deadline passed, so the diff may have given up on finding the
+// best alignment.
+pub(crate) fn exceeded(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|deadline| Instant::now() > deadline)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::{deadline_after, exceeded, Instant};
+
+    #[test]
+    fn deadlines() {
+        assert_eq!(deadline_after(0), None);
+        assert!(!exceeded(None));
+        assert!(!exceeded(deadline_after(60_000)));
+        let past = Instant::now();
+        while Instant::now() <= past {}
+        assert!(exceeded(Some(past)));
+    }
+}
@@ -0,0 +1,402 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::{transform_u32_to_array_of_u8, Hunk, HunkKind, LineDiffResult};
+
+// Every result starts with a 10 byte header: the magic bytes, the format
+// version, the record count as a big-endian u32 and the flags.
+const MAGIC: [u8; 4] = *b"LDIF";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 10;
+/// Records are LEB128 varints instead of big-endian u32s.
+const FLAG_VARINT: u8 = 1;
+/// The content is binary and there are no records.
+const FLAG_BINARY: u8 = 2;
+/// With `FLAG_BINARY`, the binary contents differ.
+const FLAG_DIFFERS: u8 = 4;
+/// The diff ran into its deadline, the markers are correct but may be
+/// coarser than they could be.
+const FLAG_APPROXIMATE: u8 = 8;
+
+/// Why `decode_line_diff` could not read a result.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum DecodeError {
+    /// The bytes do not start with the magic bytes, e.g. a legacy result.
+    BadMagic,
+    UnsupportedVersion(u8),
+    UnsupportedFlags(u8),
+    /// The bytes end in the middle of the header or a record.
+    Truncated,
+    /// A varint does not fit into a u32.
+    InvalidVarint,
+    /// A binary result claims to have records.
+    InvalidCount,
+    InvalidKind(u8),
+    /// There are bytes left after the last record.
+    TrailingBytes,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::BadMagic => write!(f, "not a line-diff result"),
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported line-diff format version {}", version)
+            }
+            DecodeError::UnsupportedFlags(flags) => {
+                write!(f, "unsupported line-diff format flags {:#04x}", flags)
+            }
+            DecodeError::Truncated => write!(f, "truncated line-diff result"),
+            DecodeError::InvalidVarint => write!(f, "invalid varint in line-diff result"),
+            DecodeError::InvalidCount => write!(f, "binary line-diff result with records"),
+            DecodeError::InvalidKind(kind) => write!(f, "invalid marker kind {}", kind),
+            DecodeError::TrailingBytes => write!(f, "trailing bytes after the last record"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+// The header followed by one record per marker: start line, end line, kind
+// byte, old start line, old end line and deleted line count.
+pub(crate) fn encode_diffs(result: &[Hunk], compact: bool, approximate: bool) -> Vec<u8> {
+    let mut magic_numbers = Vec::new();
+    write_diffs(&mut magic_numbers, result, compact, approximate);
+    magic_numbers
+}
+
+// Like `encode_diffs`, with binary results written as just the header.
+pub(crate) fn encode_result(result: &LineDiffResult, compact: bool) -> Vec<u8> {
+    let mut magic_numbers = Vec::new();
+    write_result(&mut magic_numbers, result, compact);
+    magic_numbers
+}
+
+pub(crate) fn write_result(out: &mut Vec<u8>, result: &LineDiffResult, compact: bool) {
+    match result {
+        LineDiffResult::Text {
+            markers,
+            approximate,
+        } => write_diffs(out, markers, compact, *approximate),
+        LineDiffResult::Binary { identical } => {
+            let differs = if *identical { 0 } else { FLAG_DIFFERS };
+            write_header(out, 0, FLAG_BINARY | differs);
+        }
+    }
+}
+
+// Like `encode_diffs`, replacing what is in `out` but keeping its capacity.
+pub(crate) fn write_diffs(out: &mut Vec<u8>, result: &[Hunk], compact: bool, approximate: bool) {
+    let mut flags = 0;
+    if compact {
+        flags |= FLAG_VARINT;
+    }
+    if approximate {
+        flags |= FLAG_APPROXIMATE;
+    }
+    write_header(out, result.len() as u32, flags);
+
+    for d in result {
+        if compact {
+            push_varint(out, d.start_line);
+            push_varint(out, d.end_line);
+            out.push(d.kind as u8);
+            push_varint(out, d.old_start_line);
+            push_varint(out, d.old_end_line);
+            push_varint(out, d.deleted_line_count);
+        } else {
+            out.extend(transform_u32_to_array_of_u8(d.start_line));
+            out.extend(transform_u32_to_array_of_u8(d.end_line));
+            out.push(d.kind as u8);
+            out.extend(transform_u32_to_array_of_u8(d.old_start_line));
+            out.extend(transform_u32_to_array_of_u8(d.old_end_line));
+            out.extend(transform_u32_to_array_of_u8(d.deleted_line_count));
+        }
+    }
+}
+
+// The 0.1.7 format: no header and 9 bytes per marker, the start line, end
+// line and kind byte.
+pub(crate) fn encode_legacy_diffs(result: &[Hunk]) -> Vec<u8> {
+    let mut magic_numbers: std::vec::Vec<u8> = Vec::new();
+
+    for d in result.iter() {
+        let start_line_bytes = transform_u32_to_array_of_u8(d.start_line);
+        magic_numbers.extend(start_line_bytes);
+        let end_line_bytes = transform_u32_to_array_of_u8(d.end_line);
+        magic_numbers.extend(end_line_bytes);
+        magic_numbers.push(d.kind as u8);
+    }
+
+    magic_numbers
+}
+
+fn write_header(out: &mut Vec<u8>, count: u32, flags: u8) {
+    out.clear();
+    out.extend(MAGIC);
+    out.push(VERSION);
+    out.extend(transform_u32_to_array_of_u8(count));
+    out.push(flags);
+}
+
+fn push_varint(out: &mut Vec<u8>, mut x: u32) {
+    while x >= 0x80 {
+        out.push((x as u8 & 0x7f) | 0x80);
+        x >>= 7;
+    }
+    out.push(x as u8);
+}
+
+/// Reads a `line_diff` result in either encoding back.
+pub fn decode_line_diff(bytes: &[u8]) -> Result<LineDiffResult, DecodeError> {
+    if bytes.len() < MAGIC.len() || bytes[..MAGIC.len()] != MAGIC {
+        return Err(DecodeError::BadMagic);
+    }
+    if bytes.len() < HEADER_LEN {
+        return Err(DecodeError::Truncated);
+    }
+    if bytes[4] != VERSION {
+        return Err(DecodeError::UnsupportedVersion(bytes[4]));
+    }
+    let count = u32::from_be_bytes([bytes[5], bytes[6], bytes[7], bytes[8]]);
+    let flags = bytes[9];
+    let binary = flags & FLAG_BINARY != 0;
+    if flags & !(FLAG_VARINT | FLAG_BINARY | FLAG_DIFFERS | FLAG_APPROXIMATE) != 0
+        || (flags & FLAG_DIFFERS != 0 && !binary)
+        || (flags & FLAG_APPROXIMATE != 0 && binary)
+    {
+        return Err(DecodeError::UnsupportedFlags(flags));
+    }
+    if binary {
+        if count != 0 {
+            return Err(DecodeError::InvalidCount);
+        }
+        if bytes.len() > HEADER_LEN {
+            return Err(DecodeError::TrailingBytes);
+        }
+        return Ok(LineDiffResult::Binary {
+            identical: flags & FLAG_DIFFERS == 0,
+        });
+    }
+
+    let mut reader = Reader {
+        bytes: &bytes[HEADER_LEN..],
+        varint: flags & FLAG_VARINT != 0,
+    };
+    // every record takes at least 6 bytes, so a bogus count cannot make us
+    // allocate more than the input
+    let mut result = Vec::with_capacity((count as usize).min(reader.bytes.len() / 6));
+    for _ in 0..count {
+        result.push(Hunk {
+            start_line: reader.u32()?,
+            end_line: reader.u32()?,
+            kind: reader.kind()?,
+            old_start_line: reader.u32()?,
+            old_end_line: reader.u32()?,
+            deleted_line_count: reader.u32()?,
+        });
+    }
+    if !reader.bytes.is_empty() {
+        return Err(DecodeError::TrailingBytes);
+    }
+    Ok(LineDiffResult::Text {
+        markers: result,
+        approximate: flags & FLAG_APPROXIMATE != 0,
+    })
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    varint: bool,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, DecodeError> {
+        let (&byte, rest) = self.bytes.split_first().ok_or(DecodeError::Truncated)?;
+        self.bytes = rest;
+        Ok(byte)
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        if !self.varint {
+            let mut be = [0; 4];
+            for b in be.iter_mut() {
+                *b = self.byte()?;
+            }
+            return Ok(u32::from_be_bytes(be));
+        }
+        let mut value: u64 = 0;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return u32::try_from(value).map_err(|_| DecodeError::InvalidVarint);
+            }
+        }
+        Err(DecodeError::InvalidVarint)
+    }
+
+    fn kind(&mut self) -> Result<HunkKind, DecodeError> {
+        match self.byte()? {
+            1 => Ok(HunkKind::Add),
+            2 => Ok(HunkKind::Delete),
+            3 => Ok(HunkKind::Modify),
+            4 => Ok(HunkKind::Conflict),
+            5 => Ok(HunkKind::MovedFrom),
+            6 => Ok(HunkKind::MovedTo),
+            kind => Err(DecodeError::InvalidKind(kind)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use crate::format::{
+        decode_line_diff, encode_diffs, encode_result, DecodeError, FLAG_APPROXIMATE, FLAG_BINARY,
+        FLAG_DIFFERS, FLAG_VARINT, HEADER_LEN, MAGIC, VERSION,
+    };
+    use crate::{diff, Hunk, HunkKind, LineDiffResult};
+
+    #[test]
+    fn round_trip() {
+        let result = diff("a\nb\nc\n", "A\nc\nd\n");
+        let fixed = encode_diffs(&result, false, false);
+        let compact = encode_diffs(&result, true, false);
+        assert_eq!(fixed.len(), 10 + 21 * result.len());
+        assert!(compact.len() < fixed.len());
+        let result = LineDiffResult::Text {
+            markers: result,
+            approximate: false,
+        };
+        assert_eq!(decode_line_diff(&fixed).unwrap(), result);
+        assert_eq!(decode_line_diff(&compact).unwrap(), result);
+    }
+
+    #[test]
+    fn large_varints() {
+        let result = vec![Hunk {
+            start_line: 300,
+            end_line: u32::MAX,
+            kind: HunkKind::Delete,
+            old_start_line: 127,
+            old_end_line: 128,
+            deleted_line_count: 0,
+        }];
+        let out = encode_diffs(&result, true, false);
+        assert_eq!(
+            out[HEADER_LEN..],
+            [172, 2, 255, 255, 255, 255, 15, 2, 127, 128, 1, 0]
+        );
+        assert_eq!(
+            decode_line_diff(&out).unwrap(),
+            LineDiffResult::Text {
+                markers: result,
+                approximate: false
+            }
+        );
+    }
+
+    #[test]
+    fn approximate() {
+        let result = LineDiffResult::Text {
+            markers: diff("a\n", "b\n"),
+            approximate: true,
+        };
+        let out = encode_result(&result, false);
+        assert_eq!(out[9], FLAG_APPROXIMATE);
+        assert_eq!(decode_line_diff(&out).unwrap(), result);
+    }
+
+    #[test]
+    fn binary() {
+        for identical in [false, true] {
+            let result = LineDiffResult::Binary { identical };
+            let out = encode_result(&result, true);
+            let flags = if identical { 2 } else { 6 };
+            assert_eq!(out, [b'L', b'D', b'I', b'F', 1, 0, 0, 0, 0, flags]);
+            assert_eq!(decode_line_diff(&out).unwrap(), result);
+        }
+    }
+
+    #[test]
+    fn header() {
+        let out = encode_diffs(&diff("a\n", "b\n"), true, false);
+        assert_eq!(
+            out[..HEADER_LEN],
+            [b'L', b'D', b'I', b'F', 1, 0, 0, 0, 1, 1]
+        );
+    }
+
+    #[test]
+    fn decode_errors() {
+        let out = encode_diffs(&diff("a\n", "b\n"), false, false);
+        assert_eq!(decode_line_diff(&out[10..]), Err(DecodeError::BadMagic));
+        assert_eq!(
+            decode_line_diff(&out[..out.len() - 1]),
+            Err(DecodeError::Truncated)
+        );
+
+        let mut bad = out.clone();
+        bad[4] = 2;
+        assert_eq!(
+            decode_line_diff(&bad),
+            Err(DecodeError::UnsupportedVersion(2))
+        );
+        let mut bad = out.clone();
+        bad[9] = 16;
+        assert_eq!(
+            decode_line_diff(&bad),
+            Err(DecodeError::UnsupportedFlags(16))
+        );
+        let mut bad = encode_result(&LineDiffResult::Binary { identical: true }, false);
+        bad[9] |= FLAG_APPROXIMATE;
+        assert_eq!(
+            decode_line_diff(&bad),
+            Err(DecodeError::UnsupportedFlags(10))
+        );
+        let mut bad = out.clone();
+        bad[9] = 4;
+        assert_eq!(
+            decode_line_diff(&bad),
+            Err(DecodeError::UnsupportedFlags(4))
+        );
+        let mut bad = out.clone();
+        bad[9] = 2;
+        assert_eq!(decode_line_diff(&bad), Err(DecodeError::InvalidCount));
+        let mut bad = out.clone();
+        bad[18] = 9;
+        assert_eq!(decode_line_diff(&bad), Err(DecodeError::InvalidKind(9)));
+        let mut bad = out;
+        bad.push(0);
+        assert_eq!(decode_line_diff(&bad), Err(DecodeError::TrailingBytes));
+
+        let mut bad = encode_diffs(&[], true, false);
+        bad[8] = 1;
+        bad.extend([0xff, 0xff, 0xff, 0xff, 0x1f]);
+        assert_eq!(decode_line_diff(&bad), Err(DecodeError::InvalidVarint));
+    }
+
+    // js/line_diff_format.ts is generated from the constants above, rerun
+    // with UPDATE_TS_DECODER=1 after changing the format.
+    #[test]
+    fn typescript_decoder_is_up_to_date() {
+        let magic: Vec<String> = MAGIC.iter().map(|b| format!("{:#04x}", b)).collect();
+        let source = include_str!("format.ts.in")
+            .replace("$MAGIC_TEXT", std::str::from_utf8(&MAGIC).unwrap())
+            .replace("$MAGIC", &magic.join(", "))
+            .replace("$VERSION", &VERSION.to_string())
+            .replace("$HEADER_LEN", &HEADER_LEN.to_string())
+            .replace("$FLAG_VARINT", &FLAG_VARINT.to_string())
+            .replace("$FLAG_BINARY", &FLAG_BINARY.to_string())
+            .replace("$FLAG_DIFFERS", &FLAG_DIFFERS.to_string())
+            .replace("$FLAG_APPROXIMATE", &FLAG_APPROXIMATE.to_string());
+
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/js/line_diff_format.ts");
+        if env::var_os("UPDATE_TS_DECODER").is_some() {
+            fs::write(path, &source).unwrap();
+        }
+        assert_eq!(fs::read_to_string(path).unwrap(), source);
+    }
+}
@@ -0,0 +1,229 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use git2::{ErrorCode, Repository};
+
+use crate::binary::diff_bytes_result;
+use crate::{DiffOptions, LineDiffResult};
+
+/// What `diff_working_file` compares a file in the working tree with.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum GitBase {
+    /// The file as of the commit `HEAD` points at.
+    Head,
+    /// The staged file.
+    Index,
+}
+
+/// Why `diff_working_file` could not diff a file.
+#[derive(Debug)]
+pub enum GitError {
+    /// No repository was found or it could not be read.
+    Git(git2::Error),
+    /// The working tree file could not be read.
+    Io(io::Error),
+    /// The repository has no working tree.
+    BareRepository,
+    /// The file is not inside the working tree of the repository.
+    OutsideWorkdir(PathBuf),
+}
+
+impl fmt::Display for GitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GitError::Git(error) => write!(f, "{}", error.message()),
+            GitError::Io(error) => write!(f, "{}", error),
+            GitError::BareRepository => write!(f, "the repository has no working tree"),
+            GitError::OutsideWorkdir(path) => {
+                write!(f, "{} is outside the working tree", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for GitError {}
+
+impl From<git2::Error> for GitError {
+    fn from(error: git2::Error) -> GitError {
+        GitError::Git(error)
+    }
+}
+
+impl From<io::Error> for GitError {
+    fn from(error: io::Error) -> GitError {
+        GitError::Io(error)
+    }
+}
+
+/// Diffs a file in the working tree against its `HEAD` or staged version,
+/// without running `git`. Gives the same result as `line_diff_with_options`
+/// with the two contents.
+///
+/// The repository is found from `repo_dir` like `git` does, `path` is
+/// relative to `repo_dir`. A file that is not in `HEAD` or the index yet is
+/// diffed against an empty file, so all its lines are added. The contents are
+/// compared as stored, without line ending conversion or other git filters.
+pub fn diff_working_file(
+    repo_dir: &Path,
+    path: &Path,
+    base: GitBase,
+    options: &DiffOptions,
+) -> Result<LineDiffResult, GitError> {
+    let repo = Repository::discover(repo_dir)?;
+    let workdir = repo.workdir().ok_or(GitError::BareRepository)?;
+    let file = repo_dir.join(path);
+    let working = fs::read(&file)?;
+    let relative = fs::canonicalize(&file)?
+        .strip_prefix(fs::canonicalize(workdir)?)
+        .map(Path::to_path_buf)
+        .map_err(|_| GitError::OutsideWorkdir(file))?;
+    let base = match base {
+        GitBase::Head => head_contents(&repo, &relative)?,
+        GitBase::Index => index_contents(&repo, &relative)?,
+    };
+    Ok(diff_bytes_result(
+        base.as_deref().unwrap_or_default(),
+        &working,
+        options,
+    ))
+}
+
+// `None` when there is no commit yet or the file is not in it.
+fn head_contents(repo: &Repository, path: &Path) -> Result<Option<Vec<u8>>, git2::Error> {
+    let tree = match repo.head() {
+        Ok(head) => head.peel_to_tree()?,
+        Err(error) if error.code() == ErrorCode::UnbornBranch => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    let entry = match tree.get_path(path) {
+        Ok(entry) => entry,
+        Err(error) if error.code() == ErrorCode::NotFound => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    let blob = entry.to_object(repo)?.peel_to_blob()?;
+    Ok(Some(blob.content().to_vec()))
+}
+
+// `None` when the file is not staged.
+fn index_contents(repo: &Repository, path: &Path) -> Result<Option<Vec<u8>>, git2::Error> {
+    let entry = match repo.index()?.get_path(path, 0) {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+    Ok(Some(repo.find_blob(entry.id)?.content().to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use git2::{Repository, Signature};
+    use tempfile::TempDir;
+
+    use crate::git::{diff_working_file, GitBase, GitError};
+    use crate::{DiffOptions, Hunk, HunkKind, LineDiffResult};
+
+    fn stage(repo: &Repository, path: &str, contents: &str) {
+        fs::write(repo.workdir().unwrap().join(path), contents).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(path)).unwrap();
+        index.write().unwrap();
+    }
+
+    fn commit(repo: &Repository) {
+        let mut index = repo.index().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("test", "test@example.com").unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "test", &tree, &[])
+            .unwrap();
+    }
+
+    fn markers(dir: &Path, path: &str, base: GitBase) -> Vec<Hunk> {
+        match diff_working_file(dir, Path::new(path), base, &DiffOptions::default()).unwrap() {
+            LineDiffResult::Text { markers, .. } => markers,
+            LineDiffResult::Binary { .. } => panic!("not text"),
+        }
+    }
+
+    fn marker(kind: HunkKind, line: u32, old_line: u32, deleted_line_count: u32) -> Hunk {
+        Hunk {
+            start_line: line,
+            end_line: line,
+            kind,
+            old_start_line: old_line,
+            old_end_line: old_line,
+            deleted_line_count,
+        }
+    }
+
+    #[test]
+    fn diffs_against_head_and_index() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        stage(&repo, "src/main.rs", "a\nb\nc\n");
+        commit(&repo);
+        stage(&repo, "src/main.rs", "a\nB\nc\n");
+        fs::write(dir.path().join("src/main.rs"), "a\nB\nc\nd\n").unwrap();
+
+        assert_eq!(
+            markers(dir.path(), "src/main.rs", GitBase::Head),
+            vec![
+                marker(HunkKind::Modify, 2, 2, 1),
+                marker(HunkKind::Add, 4, 4, 0)
+            ]
+        );
+        assert_eq!(
+            markers(dir.path(), "src/main.rs", GitBase::Index),
+            vec![marker(HunkKind::Add, 4, 4, 0)]
+        );
+        // from a subdirectory, like `git` run there
+        assert_eq!(
+            markers(&dir.path().join("src"), "main.rs", GitBase::Index),
+            vec![marker(HunkKind::Add, 4, 4, 0)]
+        );
+    }
+
+    #[test]
+    fn new_files_are_added() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        stage(&repo, "staged.txt", "a\nb\n");
+
+        let added = vec![Hunk {
+            start_line: 1,
+            end_line: 2,
+            kind: HunkKind::Add,
+            old_start_line: 1,
+            old_end_line: 1,
+            deleted_line_count: 0,
+        }];
+        // no commit yet
+        assert_eq!(markers(dir.path(), "staged.txt", GitBase::Head), added);
+        assert_eq!(markers(dir.path(), "staged.txt", GitBase::Index), vec![]);
+        commit(&repo);
+        fs::write(dir.path().join("untracked.txt"), "a\nb\n").unwrap();
+        assert_eq!(markers(dir.path(), "untracked.txt", GitBase::Head), added);
+        assert_eq!(markers(dir.path(), "untracked.txt", GitBase::Index), added);
+    }
+
+    #[test]
+    fn errors() {
+        let dir = TempDir::new().unwrap();
+        let options = DiffOptions::default();
+        let missing = diff_working_file(dir.path(), Path::new("a.txt"), GitBase::Head, &options);
+        assert!(matches!(missing, Err(GitError::Git(_))));
+
+        Repository::init(dir.path()).unwrap();
+        let missing = diff_working_file(dir.path(), Path::new("a.txt"), GitBase::Head, &options);
+        assert!(matches!(missing, Err(GitError::Io(_))));
+
+        let bare = TempDir::new().unwrap();
+        Repository::init_bare(bare.path()).unwrap();
+        let result = diff_working_file(bare.path(), Path::new("a.txt"), GitBase::Head, &options);
+        assert!(matches!(result, Err(GitError::BareRepository)));
+    }
+}
@@ -0,0 +1,433 @@
+use std::ops::Range;
+
+use serde::Serialize;
+use similar::{group_diff_ops, ChangeTag, DiffOp};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::binary::is_binary;
+use crate::clock::deadline_after;
+use crate::lines::split_lines;
+use crate::{diff_line_tags, DiffOptions};
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen(typescript_custom_section)]
+const DIFF_HUNK_TS: &'static str = r#"
+export interface HunkLine {
+  tag: "equal" | "delete" | "insert";
+  /** 1-based, missing for inserted lines, also ignored ones. */
+  oldLine?: number;
+  /** 1-based, missing for deleted lines, also ignored ones. */
+  newLine?: number;
+  text: string;
+}
+
+export interface DiffHunk {
+  oldStart: number;
+  oldLength: number;
+  newStart: number;
+  newLength: number;
+  lines: HunkLine[];
+}
+"#;
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "DiffHunk[]")]
+    pub type DiffHunkArray;
+}
+
+/// Whether a `HunkLine` is context, removed from the old text or added in
+/// the new one.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineTag {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// A line of a `DiffHunk`, with its 1-based line numbers in the documents
+/// it is part of.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HunkLine {
+    pub tag: LineTag,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_line: Option<u32>,
+    /// The line with its line ending. Context lines are taken from the new
+    /// text.
+    pub text: String,
+}
+
+/// A group of changes with the context lines around them, like a hunk of a
+/// unified diff.
+///
+/// The ranges are those of the `@@ -old_start,old_length
+/// +new_start,new_length @@` header: 1-based, and for an empty range the
+/// line before it.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffHunk {
+    pub old_start: u32,
+    #[serde(rename = "oldLength")]
+    pub old_len: u32,
+    pub new_start: u32,
+    #[serde(rename = "newLength")]
+    pub new_len: u32,
+    pub lines: Vec<HunkLine>,
+}
+
+/// Groups the changes into git style hunks with up to `context` equal lines
+/// around each, aligned like `line_diff_with_options` with the same options.
+///
+/// Changes that are closer than twice `context` lines share a hunk. Changes
+/// the options ignore are grouped like `unified_diff_with_options` does,
+/// as context: an ignored deleted line is an `Equal` line without a new line
+/// and an ignored inserted line one without an old line. Binary content has
+/// no hunks.
+pub fn diff_hunks(
+    old_text: &str,
+    new_text: &str,
+    context: u32,
+    options: &DiffOptions,
+) -> Vec<DiffHunk> {
+    if is_binary(old_text.as_bytes()) || is_binary(new_text.as_bytes()) {
+        return Vec::new();
+    }
+    let old_lines = split_lines(old_text);
+    let new_lines = split_lines(new_text);
+    patch_hunks(&old_lines, &new_lines, context, options)
+        .iter()
+        .map(|hunk| to_hunk(hunk, &old_lines, &new_lines))
+        .collect()
+}
+
+/// Like `diff_hunks`, as `DiffHunk` objects.
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn diff_hunk_objects(
+    old_text: &str,
+    new_text: &str,
+    context: u32,
+    options: &DiffOptions,
+) -> Result<DiffHunkArray, JsValue> {
+    let hunks = diff_hunks(old_text, new_text, context, options);
+    Ok(serde_wasm_bindgen::to_value(&hunks)?.unchecked_into())
+}
+
+// A line of a `PatchHunk`, with its 0-based line in each text it is in.
+// Changes the options ignore are `Equal`.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct PatchLine {
+    pub(crate) tag: LineTag,
+    pub(crate) old: Option<usize>,
+    pub(crate) new: Option<usize>,
+}
+
+// A hunk of the patch that turns the old text into the new one with only the
+// changes the options do not ignore. `old_range` and `patched_range` are the
+// 0-based lines of its `@@` header, in the old text and in the old text with
+// the patch applied. `new_start` is its first line in the new text, which also
+// has the ignored inserted lines.
+pub(crate) struct PatchHunk {
+    pub(crate) old_range: Range<usize>,
+    pub(crate) patched_range: Range<usize>,
+    pub(crate) new_start: usize,
+    pub(crate) lines: Vec<PatchLine>,
+}
+
+// The hunks of `diff_hunks` and `unified_diff_with_options`. The changes are
+// grouped by similar's `group_diff_ops` over the patch, in which ignored
+// deleted lines are context and ignored inserted lines are left out, so only
+// changes that are not ignored get a hunk and context around them.
+pub(crate) fn patch_hunks(
+    old_lines: &[&str],
+    new_lines: &[&str],
+    context: u32,
+    options: &DiffOptions,
+) -> Vec<PatchHunk> {
+    let deadline = deadline_after(options.timeout_ms);
+    let tags = diff_line_tags(old_lines, new_lines, options, deadline, None);
+
+    let mut lines = Vec::with_capacity(tags.len());
+    // how many new lines come before each of `lines`
+    let mut new_before = Vec::with_capacity(tags.len());
+    // which of `lines` each old line and each line of the patched text is
+    let mut old_at = Vec::with_capacity(old_lines.len());
+    let mut patched_at = Vec::with_capacity(new_lines.len());
+    let mut ops: Vec<DiffOp> = Vec::new();
+    let (mut old, mut new) = (0, 0);
+    for (tag, ignored) in tags {
+        let changed = |tag| if ignored { LineTag::Equal } else { tag };
+        let line = match tag {
+            ChangeTag::Equal => PatchLine {
+                tag: LineTag::Equal,
+                old: Some(old),
+                new: Some(new),
+            },
+            ChangeTag::Delete => PatchLine {
+                tag: changed(LineTag::Delete),
+                old: Some(old),
+                new: None,
+            },
+            ChangeTag::Insert => PatchLine {
+                tag: changed(LineTag::Insert),
+                old: None,
+                new: Some(new),
+            },
+        };
+        let in_old = line.old.is_some();
+        let in_patched = line.tag == LineTag::Insert || (in_old && line.tag == LineTag::Equal);
+        if in_old || in_patched {
+            push_op(&mut ops, line.tag, old_at.len(), patched_at.len());
+        }
+        if in_old {
+            old_at.push(lines.len());
+            old += 1;
+        }
+        if in_patched {
+            patched_at.push(lines.len());
+        }
+        new_before.push(new);
+        if line.new.is_some() {
+            new += 1;
+        }
+        lines.push(line);
+    }
+
+    group_diff_ops(ops, context as usize)
+        .into_iter()
+        .map(|group| {
+            let (first, last) = (&group[0], &group[group.len() - 1]);
+            // the first and last of `lines` the group covers, without context
+            // trimmed down to nothing
+            let covered: Vec<&DiffOp> = group
+                .iter()
+                .filter(|op| !op.old_range().is_empty() || !op.new_range().is_empty())
+                .collect();
+            let (first_covered, last_covered) = (covered[0], covered[covered.len() - 1]);
+            let start = match first_covered.old_range() {
+                old if old.is_empty() => patched_at[first_covered.new_range().start],
+                old => old_at[old.start],
+            };
+            let end = match last_covered.old_range() {
+                old if old.is_empty() => patched_at[last_covered.new_range().end - 1],
+                old => old_at[old.end - 1],
+            };
+            PatchHunk {
+                old_range: first.old_range().start..last.old_range().end,
+                patched_range: first.new_range().start..last.new_range().end,
+                new_start: new_before[start],
+                lines: lines[start..=end].to_vec(),
+            }
+        })
+        .collect()
+}
+
+// Adds a line of the patch at `old_index` and `patched_index` to `ops`,
+// extending the last op when it has the same tag.
+fn push_op(ops: &mut Vec<DiffOp>, tag: LineTag, old_index: usize, patched_index: usize) {
+    match (ops.last_mut(), tag) {
+        (Some(DiffOp::Equal { len, .. }), LineTag::Equal) => *len += 1,
+        (Some(DiffOp::Delete { old_len, .. }), LineTag::Delete) => *old_len += 1,
+        (Some(DiffOp::Insert { new_len, .. }), LineTag::Insert) => *new_len += 1,
+        (_, LineTag::Equal) => ops.push(DiffOp::Equal {
+            old_index,
+            new_index: patched_index,
+            len: 1,
+        }),
+        (_, LineTag::Delete) => ops.push(DiffOp::Delete {
+            old_index,
+            old_len: 1,
+            new_index: patched_index,
+        }),
+        (_, LineTag::Insert) => ops.push(DiffOp::Insert {
+            old_index,
+            new_index: patched_index,
+            new_len: 1,
+        }),
+    }
+}
+
+fn to_hunk(hunk: &PatchHunk, old_lines: &[&str], new_lines: &[&str]) -> DiffHunk {
+    let lines: Vec<HunkLine> = hunk
+        .lines
+        .iter()
+        .map(|line| HunkLine {
+            tag: line.tag,
+            old_line: line.old.map(|i| i as u32 + 1),
+            new_line: line.new.map(|i| i as u32 + 1),
+            text: match line.new {
+                Some(new) => new_lines[new],
+                None => old_lines[line.old.unwrap()],
+            }
+            .to_string(),
+        })
+        .collect();
+
+    let new_len = lines.iter().filter(|line| line.new_line.is_some()).count();
+    DiffHunk {
+        old_start: header_start(hunk.old_range.start, hunk.old_range.len()),
+        old_len: hunk.old_range.len() as u32,
+        new_start: header_start(hunk.new_start, new_len),
+        new_len: new_len as u32,
+        lines,
+    }
+}
+
+// `start` is 0-based. An empty range names the line before it.
+fn header_start(start: usize, len: usize) -> u32 {
+    if len == 0 {
+        start as u32
+    } else {
+        start as u32 + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hunks::{diff_hunks, DiffHunk, HunkLine, LineTag};
+    use crate::unified::unified_diff_with_options;
+    use crate::DiffOptions;
+
+    fn line(tag: LineTag, old_line: Option<u32>, new_line: Option<u32>, text: &str) -> HunkLine {
+        HunkLine {
+            tag,
+            old_line,
+            new_line,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn groups_changes_with_context() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let new = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\neleven\n";
+        let hunks = diff_hunks(old, new, 1, &DiffOptions::default());
+        assert_eq!(
+            hunks,
+            vec![
+                DiffHunk {
+                    old_start: 2,
+                    old_len: 3,
+                    new_start: 2,
+                    new_len: 3,
+                    lines: vec![
+                        line(LineTag::Equal, Some(2), Some(2), "2\n"),
+                        line(LineTag::Delete, Some(3), None, "3\n"),
+                        line(LineTag::Insert, None, Some(3), "three\n"),
+                        line(LineTag::Equal, Some(4), Some(4), "4\n"),
+                    ],
+                },
+                DiffHunk {
+                    old_start: 10,
+                    old_len: 1,
+                    new_start: 10,
+                    new_len: 2,
+                    lines: vec![
+                        line(LineTag::Equal, Some(10), Some(10), "10\n"),
+                        line(LineTag::Insert, None, Some(11), "eleven\n"),
+                    ],
+                },
+            ]
+        );
+        // with more context both changes share a hunk
+        assert_eq!(diff_hunks(old, new, 4, &DiffOptions::default()).len(), 1);
+        assert_eq!(diff_hunks(old, old, 3, &DiffOptions::default()), vec![]);
+    }
+
+    // The `@@` header and the ' ', '-' and '+' of each line, like a unified
+    // diff. With ignored inserted lines the new range is that of the new text
+    // rather than of the old text with the patch applied, so it is only
+    // compared for `DiffOptions::default()`.
+    fn as_unified(hunks: &[DiffHunk], new_range: bool) -> Vec<(String, String)> {
+        let range = |start, len| match len {
+            1 => format!("{}", start),
+            _ => format!("{},{}", start, len),
+        };
+        hunks
+            .iter()
+            .map(|hunk| {
+                let mut header = format!("@@ -{}", range(hunk.old_start, hunk.old_len));
+                if new_range {
+                    header += &format!(" +{} @@", range(hunk.new_start, hunk.new_len));
+                }
+                let kinds = hunk
+                    .lines
+                    .iter()
+                    .filter_map(|line| match (line.tag, line.old_line) {
+                        (LineTag::Equal, Some(_)) => Some(' '),
+                        (LineTag::Equal, None) => None,
+                        (LineTag::Delete, _) => Some('-'),
+                        (LineTag::Insert, _) => Some('+'),
+                    })
+                    .collect();
+                (header, kinds)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_hunks_as_unified_diff() {
+        let ignoring = DiffOptions {
+            ignore_whitespace: true,
+            ignore_blank_lines: true,
+            ..DiffOptions::default()
+        };
+        for (options, new_range) in &[(DiffOptions::default(), true), (ignoring, false)] {
+            for (old, new) in &[
+                ("a\nb\nc\n", "a\nc\n"),
+                ("", "a\nb\n"),
+                ("a\nb\n", ""),
+                ("x\n1\n2\n3\n4\n5\n6\n7\n8\n", "1\n2\n3\n4\n5\n6\n7\n8\ny\n"),
+                ("a\nb\nc\nd\n", "a\n\nb\nC\nd\n"),
+                (
+                    "a b\n\nc\nd\n1\n2\n3\n4\n5\n",
+                    "a  b\nc\nD\n1\n2\n\n3\n4\n5\n6\n",
+                ),
+            ] {
+                for context in 0..4 {
+                    let unified = unified_diff_with_options(old, new, context, "a", "b", options);
+                    let mut expected: Vec<(String, String)> = Vec::new();
+                    for line in unified.lines().skip(2) {
+                        if line.starts_with("@@") {
+                            let end = match new_range {
+                                true => line.len(),
+                                false => line.find(" +").unwrap(),
+                            };
+                            expected.push((line[..end].to_string(), String::new()));
+                        } else {
+                            expected.last_mut().unwrap().1.push_str(&line[..1]);
+                        }
+                    }
+                    let hunks = diff_hunks(old, new, context, options);
+                    assert_eq!(
+                        as_unified(&hunks, *new_range),
+                        expected,
+                        "{:?} -> {:?} with {} context lines",
+                        old,
+                        new,
+                        context
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ignored_blank_lines_have_no_hunk() {
+        let options = DiffOptions {
+            ignore_blank_lines: true,
+            ..DiffOptions::default()
+        };
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\n";
+        let new = "a\n\nb\nc\nd\ne\nf\ng\nH\n";
+        let hunks = diff_hunks(old, new, 1, &options);
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].new_start, 8);
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::VecDeque;
+
+use similar::{ChangeTag, TextDiff};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::clock::deadline_after;
+use crate::{split_lines, text_changes, transform_u32_to_array_of_u8, DiffOptions, HunkKind};
+
+/// What an inline diff compares modified lines by.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum InlineGranularity {
+    Word = 0,
+    Char = 1,
+}
+
+/// Changed columns within one modified line of the new document, using the
+/// same add/delete/modify semantics as the gutter markers. A delete is a
+/// caret with `start_column == end_column`.
+#[derive(Debug, PartialEq)]
+pub(crate) struct InlineDiff {
+    pub(crate) line: u32,
+    pub(crate) start_column: u32,
+    pub(crate) end_column: u32,
+    pub(crate) kind: HunkKind,
+}
+
+/// Returns the changed columns of every line covered by a modify marker,
+/// as 13 byte records: line, start column and end column as big-endian
+/// u32s followed by the kind byte.
+///
+/// Lines are 1-based, columns are 0-based UTF-16 offsets so they can be
+/// used directly on JS strings. The end column is exclusive.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn inline_diff(
+    old_text: &str,
+    new_text: &str,
+    options: &DiffOptions,
+    granularity: InlineGranularity,
+) -> Vec<u8> {
+    let result = diff_inline(old_text, new_text, options, granularity);
+
+    let mut magic_numbers: std::vec::Vec<u8> = Vec::new();
+    for d in result.iter() {
+        magic_numbers.extend(transform_u32_to_array_of_u8(d.line));
+        magic_numbers.extend(transform_u32_to_array_of_u8(d.start_column));
+        magic_numbers.extend(transform_u32_to_array_of_u8(d.end_column));
+        magic_numbers.push(d.kind as u8);
+    }
+    magic_numbers
+}
+
+pub(crate) fn diff_inline(
+    old_text: &str,
+    new_text: &str,
+    options: &DiffOptions,
+    granularity: InlineGranularity,
+) -> Vec<InlineDiff> {
+    let old_lines = split_lines(old_text);
+    let new_lines = split_lines(new_text);
+    let deadline = deadline_after(options.timeout_ms);
+    // moved lines are ignored, so they are never paired
+    let changes = text_changes(&old_lines, &new_lines, options, deadline, None);
+
+    let mut result = Vec::new();
+    for (old_index, new_index) in modified_line_pairs(&changes.tags, &changes.breaks) {
+        diff_line(
+            old_lines[old_index],
+            new_lines[new_index],
+            new_index as u32 + 1,
+            options,
+            granularity,
+            &mut result,
+        );
+    }
+    result
+}
+
+// Pairs inserted lines with pending deleted lines the same way `diff`
+// turns them into modify markers: in order, by count, dropping the pending
+// deletes at `breaks` and ignored deletes.
+fn modified_line_pairs(tags: &[(ChangeTag, bool)], breaks: &[usize]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    let mut pending_deletes = VecDeque::new();
+    let mut old_index = 0;
+    let mut new_index = 0;
+
+    for (index, &(tag, ignored)) in tags.iter().enumerate() {
+        if breaks.binary_search(&index).is_ok() {
+            pending_deletes.clear();
+        }
+        match tag {
+            ChangeTag::Delete if ignored => {
+                pending_deletes.clear();
+                old_index += 1;
+            }
+            ChangeTag::Insert if ignored => new_index += 1,
+            ChangeTag::Equal => {
+                pending_deletes.clear();
+                old_index += 1;
+                new_index += 1;
+            }
+            ChangeTag::Delete => {
+                pending_deletes.push_back(old_index);
+                old_index += 1;
+            }
+            ChangeTag::Insert => {
+                if let Some(deleted) = pending_deletes.pop_front() {
+                    pairs.push((deleted, new_index));
+                }
+                new_index += 1;
+            }
+        }
+    }
+    pairs
+}
+
+fn diff_line(
+    old_line: &str,
+    new_line: &str,
+    line: u32,
+    options: &DiffOptions,
+    granularity: InlineGranularity,
+    result: &mut Vec<InlineDiff>,
+) {
+    let old_content = old_line.trim_end_matches(['\r', '\n']);
+    let new_content = new_line.trim_end_matches(['\r', '\n']);
+    let mut config = TextDiff::configure();
+    config.algorithm(options.algorithm.into());
+    let diff = match granularity {
+        InlineGranularity::Word => config.diff_words(old_content, new_content),
+        InlineGranularity::Char => config.diff_chars(old_content, new_content),
+    };
+
+    let mut column = 0;
+    let mut deleted = false;
+    let mut inserted_from = None;
+    let mut flush = |column: u32, deleted: &mut bool, inserted_from: &mut Option<u32>| {
+        let (start_column, kind) = match (inserted_from.take(), *deleted) {
+            (Some(start), true) => (start, HunkKind::Modify),
+            (Some(start), false) => (start, HunkKind::Add),
+            (None, true) => (column, HunkKind::Delete),
+            (None, false) => return,
+        };
+        *deleted = false;
+        result.push(InlineDiff {
+            line,
+            start_column,
+            end_column: column,
+            kind,
+        });
+    };
+
+    for change in diff.iter_all_changes() {
+        let value = change.value();
+        let width = value.encode_utf16().count() as u32;
+        let ignored = options.ignore_whitespace && value.trim().is_empty();
+        match change.tag() {
+            ChangeTag::Equal => {
+                flush(column, &mut deleted, &mut inserted_from);
+                column += width;
+            }
+            ChangeTag::Delete if ignored => {}
+            ChangeTag::Delete => deleted = true,
+            ChangeTag::Insert if ignored => column += width,
+            ChangeTag::Insert => {
+                inserted_from.get_or_insert(column);
+                column += width;
+            }
+        }
+    }
+    flush(column, &mut deleted, &mut inserted_from);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inline::{diff_inline, inline_diff, InlineDiff, InlineGranularity};
+    use crate::DiffOptions;
+    use crate::HunkKind;
+
+    #[test]
+    fn changed_word() {
+        let out = diff_inline(
+            "let x = 1;\n",
+            "let y = 1;\n",
+            &DiffOptions::default(),
+            InlineGranularity::Word,
+        );
+        let expected = vec![InlineDiff {
+            line: 1,
+            start_column: 4,
+            end_column: 5,
+            kind: HunkKind::Modify,
+        }];
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn added_and_deleted_words() {
+        let out = diff_inline(
+            "a\nfoo bar baz\n",
+            "a\nfoo baz qux\n",
+            &DiffOptions::default(),
+            InlineGranularity::Word,
+        );
+        let expected = vec![
+            InlineDiff {
+                line: 2,
+                start_column: 4,
+                end_column: 4,
+                kind: HunkKind::Delete,
+            },
+            InlineDiff {
+                line: 2,
+                start_column: 7,
+                end_column: 11,
+                kind: HunkKind::Add,
+            },
+        ];
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn chars_and_utf16_columns() {
+        let out = diff_inline(
+            "\u{1F600} colour\n",
+            "\u{1F600} color\n",
+            &DiffOptions::default(),
+            InlineGranularity::Char,
+        );
+        // the emoji is two UTF-16 code units
+        let expected = vec![InlineDiff {
+            line: 1,
+            start_column: 7,
+            end_column: 7,
+            kind: HunkKind::Delete,
+        }];
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn only_modified_lines() {
+        let out = diff_inline(
+            "a\nb\n",
+            "a\nB\nnew\n",
+            &DiffOptions::default(),
+            InlineGranularity::Char,
+        );
+        let expected = vec![InlineDiff {
+            line: 2,
+            start_column: 0,
+            end_column: 1,
+            kind: HunkKind::Modify,
+        }];
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn ignoring_whitespace() {
+        let options = DiffOptions {
+            ignore_whitespace: true,
+            ..DiffOptions::default()
+        };
+        let out = diff_inline("a = 1\n", "a  =  2\n", &options, InlineGranularity::Word);
+        let expected = vec![InlineDiff {
+            line: 1,
+            start_column: 6,
+            end_column: 7,
+            kind: HunkKind::Modify,
+        }];
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn moved_lines_are_not_modified() {
+        let function = "fn moved() {\n    let total = compute();\n    total\n}\n";
+        let middle = "a\nb\nc\nd\ne\nf\n";
+        let old = format!("{}{}", function, middle);
+        let new = format!("let header = 1;\n{}{}", middle, function);
+        let options = DiffOptions {
+            detect_moves: true,
+            ..DiffOptions::default()
+        };
+        assert_eq!(
+            diff_inline(&old, &new, &options, InlineGranularity::Word),
+            vec![]
+        );
+        // otherwise the first moved line is modified into the new one
+        let plain = diff_inline(&old, &new, &DiffOptions::default(), InlineGranularity::Word);
+        assert!(plain.iter().all(|diff| diff.line == 1));
+        assert!(!plain.is_empty());
+    }
+
+    #[test]
+    fn wasm_inline_diff() {
+        let out = inline_diff(
+            "let x = 1;\n",
+            "let y = 1;\n",
+            &DiffOptions::default(),
+            InlineGranularity::Word,
+        );
+        let expected: Vec<u8> = vec![0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0, 5, 3];
+        assert_eq!(out, expected);
+    }
+}
@@ -0,0 +1,166 @@
+use serde::Serialize;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::binary::is_binary;
+use crate::side_by_side::{aligned_rows, RowKind};
+use crate::{split_lines, DiffOptions};
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen(typescript_custom_section)]
+const INTERLEAVED_TS: &'static str = r#"
+export interface InterleavedRow {
+  kind: Exclude<RowKind, "collapsed">;
+  /** 1-based, only for unchanged lines and the deleted lines in between. */
+  oldLine?: number;
+  /** 1-based, missing for the deleted lines, which are not in the document. */
+  newLine?: number;
+  text: string;
+}
+"#;
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "InterleavedRow[]")]
+    pub type InterleavedRowArray;
+}
+
+/// A row of an inline view. Rows without a `new_line` are old lines that
+/// are not in the new document, to be shown as virtual lines in front of the
+/// next row that has one.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InterleavedRow {
+    /// Never `Collapsed`. A modified line has a `Modify` row for its old
+    /// text followed by one for its new text.
+    pub kind: RowKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_line: Option<u32>,
+    /// The line with its line ending.
+    pub text: String,
+}
+
+/// The lines of the new text with the deleted and replaced old lines in
+/// between where they were, from the same diff as `line_diff_with_options`
+/// with the same options. Old lines the options ignore are left out.
+/// Binary content has no rows.
+pub fn interleaved_rows(
+    old_text: &str,
+    new_text: &str,
+    options: &DiffOptions,
+) -> Vec<InterleavedRow> {
+    if is_binary(old_text.as_bytes()) || is_binary(new_text.as_bytes()) {
+        return Vec::new();
+    }
+    let old_lines = split_lines(old_text);
+    let new_lines = split_lines(new_text);
+    let row = |kind, old_line: Option<u32>, new_line: Option<u32>| {
+        let text = match new_line {
+            Some(line) => new_lines[line as usize - 1],
+            None => old_lines[old_line.unwrap() as usize - 1],
+        };
+        InterleavedRow {
+            kind,
+            old_line,
+            new_line,
+            text: text.to_string(),
+        }
+    };
+
+    let mut rows = Vec::with_capacity(new_lines.len());
+    for aligned in aligned_rows(&old_lines, &new_lines, options) {
+        let (kind, old_line, new_line) = (aligned.kind, aligned.old_line, aligned.new_line);
+        match (kind, new_line) {
+            // an ignored deleted line
+            (RowKind::Equal, None) => {}
+            (RowKind::Modify, _) => {
+                rows.push(row(kind, old_line, None));
+                rows.push(row(kind, None, new_line));
+            }
+            _ => rows.push(row(kind, old_line, new_line)),
+        }
+    }
+    rows
+}
+
+/// Like `interleaved_rows`, as `InterleavedRow` objects.
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn interleaved_row_objects(
+    old_text: &str,
+    new_text: &str,
+    options: &DiffOptions,
+) -> Result<InterleavedRowArray, JsValue> {
+    let rows = interleaved_rows(old_text, new_text, options);
+    Ok(serde_wasm_bindgen::to_value(&rows)?.unchecked_into())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interleaved::{interleaved_rows, InterleavedRow};
+    use crate::side_by_side::RowKind;
+    use crate::DiffOptions;
+
+    fn row(
+        kind: RowKind,
+        old_line: Option<u32>,
+        new_line: Option<u32>,
+        text: &str,
+    ) -> InterleavedRow {
+        InterleavedRow {
+            kind,
+            old_line,
+            new_line,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn deleted_lines_in_between() {
+        let old = "a\nb\nc\nd\ne\n";
+        let new = "a\nB\nc\nnew\nd\n";
+        assert_eq!(
+            interleaved_rows(old, new, &DiffOptions::default()),
+            vec![
+                row(RowKind::Equal, Some(1), Some(1), "a\n"),
+                row(RowKind::Modify, Some(2), None, "b\n"),
+                row(RowKind::Modify, None, Some(2), "B\n"),
+                row(RowKind::Equal, Some(3), Some(3), "c\n"),
+                row(RowKind::Add, None, Some(4), "new\n"),
+                row(RowKind::Equal, Some(4), Some(5), "d\n"),
+                row(RowKind::Delete, Some(5), None, "e\n"),
+            ]
+        );
+        assert_eq!(
+            interleaved_rows("a\0", "b\0", &DiffOptions::default()),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn ignored_deletes_are_left_out() {
+        let options = DiffOptions {
+            ignore_blank_lines: true,
+            ..DiffOptions::default()
+        };
+        let rows = interleaved_rows("a\n\nb\n", "a\nb\n", &options);
+        assert_eq!(
+            rows,
+            vec![
+                row(RowKind::Equal, Some(1), Some(1), "a\n"),
+                row(RowKind::Equal, Some(3), Some(2), "b\n"),
+            ]
+        );
+        // every new line is there once, in order
+        let rows = interleaved_rows("a\n\nb\n", "x\n\na\nb\ny\n", &options);
+        let text: String = rows
+            .iter()
+            .filter(|row| row.new_line.is_some())
+            .map(|row| row.text.as_str())
+            .collect();
+        assert_eq!(text, "x\n\na\nb\ny\n");
+    }
+}
@@ -0,0 +1,451 @@
+use std::borrow::Cow;
+use std::convert::Infallible;
+use std::hash::Hash;
+
+use rustc_hash::FxHashMap;
+use similar::algorithms::{diff_deadline, Capture, Compact, DiffHook, Replace};
+use similar::{Algorithm, DiffOp};
+
+use crate::cancel::IsCancelled;
+use crate::clock::Instant;
+
+// Diffs `old_keys` against `new_keys` like `capture_diff_slices_deadline`,
+// with the same ops, but much faster on large files: lines are interned into
+// integer ids so the algorithms compare and hash `u32`s instead of strings,
+// and the common prefix and suffix are cut off before diffing.
+//
+// Once `cancelled` returns true the diff stops and gives no ops.
+pub(crate) fn diff_interned<K: Hash + Eq>(
+    algorithm: Algorithm,
+    old_keys: &[K],
+    new_keys: &[K],
+    deadline: Option<Instant>,
+    cancelled: Option<IsCancelled>,
+) -> Vec<DiffOp> {
+    let mut prefix = common_prefix_len(old_keys, new_keys);
+    let mut suffix = common_prefix_len(
+        old_keys[prefix..].iter().rev(),
+        new_keys[prefix..].iter().rev(),
+    );
+    let (old_ids, new_ids) = match algorithm {
+        Algorithm::Myers => intern(
+            &old_keys[prefix..old_keys.len() - suffix],
+            &new_keys[prefix..new_keys.len() - suffix],
+        ),
+        // Patience needs the whole file to tell how much of it can go
+        Algorithm::Patience => {
+            let (mut old_ids, mut new_ids) = intern(old_keys, new_keys);
+            let (cut_prefix, cut_suffix) = patience_cuts(&old_ids, &new_ids, prefix, suffix);
+            prefix = cut_prefix;
+            suffix = cut_suffix;
+            for ids in [&mut old_ids, &mut new_ids] {
+                ids.truncate(ids.len() - suffix);
+                ids.drain(..prefix);
+            }
+            (old_ids, new_ids)
+        }
+        // similar's LCS fills its table from the start of the slices rather
+        // than after the prefix it skips, so cutting the prefix would change
+        // which lines it matches. It also answers identical files with a
+        // single equal op of its own.
+        Algorithm::Lcs => {
+            if prefix == old_keys.len() && prefix == new_keys.len() {
+                suffix = 0;
+            }
+            prefix = 0;
+            intern(
+                &old_keys[..old_keys.len() - suffix],
+                &new_keys[..new_keys.len() - suffix],
+            )
+        }
+    };
+    let old_end = old_keys.len() - suffix;
+    let new_end = new_keys.len() - suffix;
+
+    // similar compacts the ops of the whole file, which can slide changes
+    // into the prefix or suffix, so only the diffing itself is cut down
+    let mut d = Compact::new(Replace::new(Capture::new()), old_keys, new_keys);
+    if prefix > 0 {
+        d.equal(0, 0, prefix).unwrap();
+    }
+    let mut offset = Offset {
+        d: &mut d,
+        by: prefix,
+        cancelled,
+    };
+    let diffed = diff_deadline(
+        algorithm,
+        &mut offset,
+        &old_ids,
+        0..old_ids.len(),
+        &new_ids,
+        0..new_ids.len(),
+        deadline,
+    );
+    if diffed.is_err() {
+        return Vec::new();
+    }
+    if suffix > 0 {
+        d.equal(old_end, new_end, suffix).unwrap();
+    }
+    d.finish().unwrap();
+    d.into_inner().into_inner().into_ops()
+}
+
+// Numbers the distinct keys in order of first appearance.
+fn intern<K: Hash + Eq>(old_keys: &[K], new_keys: &[K]) -> (Vec<u32>, Vec<u32>) {
+    let mut ids: FxHashMap<&K, u32> = FxHashMap::default();
+    ids.reserve(old_keys.len());
+    let mut id_of = |key| {
+        let next = ids.len() as u32;
+        *ids.entry(key).or_insert(next)
+    };
+    let old_ids = old_keys.iter().map(&mut id_of).collect();
+    let new_ids = new_keys.iter().map(&mut id_of).collect();
+    (old_ids, new_ids)
+}
+
+// Numbers line keys like `intern`, keeping the numbers across calls so that
+// edited lines can be added as they come in.
+pub(crate) struct LineIds {
+    ids: FxHashMap<String, u32>,
+}
+
+impl LineIds {
+    pub(crate) fn new() -> LineIds {
+        LineIds {
+            ids: FxHashMap::default(),
+        }
+    }
+
+    pub(crate) fn intern<'a>(&mut self, keys: impl IntoIterator<Item = Cow<'a, str>>) -> Vec<u32> {
+        keys.into_iter()
+            .map(|key| match self.ids.get(key.as_ref()) {
+                Some(&id) => id,
+                None => {
+                    let id = self.ids.len() as u32;
+                    self.ids.insert(key.into_owned(), id);
+                    id
+                }
+            })
+            .collect()
+    }
+
+    // Once most keys are no longer used, forgets them and renumbers the
+    // rest in `in_use`, so typing does not grow the table forever.
+    pub(crate) fn forget_unused(&mut self, in_use: [&mut [u32]; 2]) {
+        let used = in_use.iter().map(|ids| ids.len()).sum::<usize>();
+        if self.ids.len() <= 2 * used + 1024 {
+            return;
+        }
+        let mut renumbered = vec![u32::MAX; self.ids.len()];
+        let mut next = 0;
+        for ids in in_use {
+            for id in ids.iter_mut() {
+                if renumbered[*id as usize] == u32::MAX {
+                    renumbered[*id as usize] = next;
+                    next += 1;
+                }
+                *id = renumbered[*id as usize];
+            }
+        }
+        self.ids.retain(|_, id| {
+            *id = renumbered[*id as usize];
+            *id != u32::MAX
+        });
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.ids.len()
+    }
+}
+
+fn common_prefix_len<'a, K: Eq + 'a>(
+    old: impl IntoIterator<Item = &'a K>,
+    new: impl IntoIterator<Item = &'a K>,
+) -> usize {
+    old.into_iter()
+        .zip(new)
+        .take_while(|(old, new)| old == new)
+        .count()
+}
+
+// How much of the common prefix and suffix can be cut off without changing
+// what Patience makes of the rest.
+//
+// Patience matches up the lines that are unique on each side, so a cut only
+// keeps its output when the lines unique in the rest of each side are the
+// same as before, and the lines unique in the cut part are the same on both
+// sides and so still get matched with each other. The gaps between matched
+// lines are filled in with Myers, which cuts the prefix of a gap first and
+// so can place a change anywhere in a run of equal lines that reaches into
+// the suffix. The suffix is only cut from a line unique on both sides on,
+// which ends the last gap before the cut either way.
+//
+// Lines like `}` that are repeated in the cut part but only show up once
+// next to a change break the first rule, so when cutting everything does not
+// work more and more of the prefix and suffix is kept until it does.
+fn patience_cuts(old_ids: &[u32], new_ids: &[u32], prefix: usize, suffix: usize) -> (usize, usize) {
+    let id_count = old_ids.iter().chain(new_ids).max().map_or(0, |&id| id + 1);
+    let old_counts = counts(old_ids, id_count);
+    let new_counts = counts(new_ids, id_count);
+    let mut rest_old_counts = vec![0; id_count as usize];
+    let mut rest_new_counts = vec![0; id_count as usize];
+
+    let mut keep = 0;
+    loop {
+        let cut_prefix = prefix.saturating_sub(keep);
+        let cut_suffix = old_ids[old_ids.len() - suffix..]
+            .iter()
+            .skip(keep)
+            .position(|&id| old_counts[id as usize] == 1 && new_counts[id as usize] == 1)
+            .map_or(0, |at| suffix - keep - at);
+        if cut_prefix == 0 && cut_suffix == 0 {
+            return (0, 0);
+        }
+
+        let rest_old = &old_ids[cut_prefix..old_ids.len() - cut_suffix];
+        let rest_new = &new_ids[cut_prefix..new_ids.len() - cut_suffix];
+        for &id in rest_old {
+            rest_old_counts[id as usize] += 1;
+        }
+        for &id in rest_new {
+            rest_new_counts[id as usize] += 1;
+        }
+        // only lines that are both cut and kept can change
+        let keeps_unique_lines = rest_old.iter().chain(rest_new).all(|&id| {
+            let (old, new) = (rest_old_counts[id as usize], rest_new_counts[id as usize]);
+            let cut = old_counts[id as usize] - old;
+            cut == 0 || (old != 1 && new != 1 && (cut > 1 || (old == 0) == (new == 0)))
+        });
+        if keeps_unique_lines {
+            return (cut_prefix, cut_suffix);
+        }
+        for &id in rest_old.iter().chain(rest_new) {
+            rest_old_counts[id as usize] = 0;
+            rest_new_counts[id as usize] = 0;
+        }
+        keep = (keep * 8).max(8);
+    }
+}
+
+// How often each id shows up.
+fn counts(ids: &[u32], id_count: u32) -> Vec<u32> {
+    let mut counts = vec![0; id_count as usize];
+    for &id in ids {
+        counts[id as usize] += 1;
+    }
+    counts
+}
+
+// Passes the ops for a slice on with indexes into the whole file, without
+// finishing the inner hook. Every op checks for cancellation, which is as
+// often as similar lets the diff be stopped.
+struct Offset<'a, D> {
+    d: &'a mut D,
+    by: usize,
+    cancelled: Option<IsCancelled<'a>>,
+}
+
+struct Cancelled;
+
+impl<D: DiffHook<Error = Infallible>> Offset<'_, D> {
+    fn check(&self) -> Result<(), Cancelled> {
+        match self.cancelled {
+            Some(cancelled) if cancelled() => Err(Cancelled),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<D: DiffHook<Error = Infallible>> DiffHook for Offset<'_, D> {
+    type Error = Cancelled;
+
+    fn equal(&mut self, old_index: usize, new_index: usize, len: usize) -> Result<(), Cancelled> {
+        self.check()?;
+        let Ok(()) = self.d.equal(old_index + self.by, new_index + self.by, len);
+        Ok(())
+    }
+
+    fn delete(
+        &mut self,
+        old_index: usize,
+        old_len: usize,
+        new_index: usize,
+    ) -> Result<(), Cancelled> {
+        self.check()?;
+        let Ok(()) = self
+            .d
+            .delete(old_index + self.by, old_len, new_index + self.by);
+        Ok(())
+    }
+
+    fn insert(
+        &mut self,
+        old_index: usize,
+        new_index: usize,
+        new_len: usize,
+    ) -> Result<(), Cancelled> {
+        self.check()?;
+        let Ok(()) = self
+            .d
+            .insert(old_index + self.by, new_index + self.by, new_len);
+        Ok(())
+    }
+
+    fn replace(
+        &mut self,
+        old_index: usize,
+        old_len: usize,
+        new_index: usize,
+        new_len: usize,
+    ) -> Result<(), Cancelled> {
+        self.check()?;
+        let Ok(()) = self
+            .d
+            .replace(old_index + self.by, old_len, new_index + self.by, new_len);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use similar::{capture_diff_slices_deadline, Algorithm, DiffableStr};
+
+    use crate::intern::diff_interned;
+    use crate::options::is_blank;
+    use crate::{collect_diffs, diff_with_options, line_tags, DiffAlgorithm, DiffOptions, Hunk};
+
+    // A small xorshift so the generated files are the same on every run.
+    struct Lines(u64);
+
+    impl Lines {
+        fn next(&mut self, bound: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % bound
+        }
+
+        // Few distinct lines, so repeated and unique lines both show up in
+        // the common prefix and suffix as well as in the changed middle, or
+        // enough for most lines to be unique.
+        fn file(&mut self) -> (Vec<String>, Vec<String>) {
+            let most = if self.next(2) == 0 { 8 } else { 40 };
+            let alphabet = 2 + self.next(most);
+            let old: Vec<String> = (0..self.next(40))
+                .map(|_| self.next(alphabet).to_string())
+                .collect();
+            let mut new = old.clone();
+            for _ in 0..self.next(6) {
+                let at = self.next(new.len() as u64 + 1) as usize;
+                match self.next(3) {
+                    0 if at < new.len() => {
+                        new.remove(at);
+                    }
+                    1 if at < new.len() => new[at] = self.next(alphabet + 2).to_string(),
+                    _ => new.insert(at, self.next(alphabet + 2).to_string()),
+                }
+            }
+            (old, new)
+        }
+    }
+
+    #[test]
+    fn same_ops_as_similar() {
+        let mut lines = Lines(0x2545_f491_4f6c_dd1d);
+        for _ in 0..5_000 {
+            let (old, new) = lines.file();
+            for &algorithm in &[Algorithm::Myers, Algorithm::Patience, Algorithm::Lcs] {
+                assert_eq!(
+                    diff_interned(algorithm, &old, &new, None, None),
+                    capture_diff_slices_deadline(algorithm, &old, &new, None),
+                    "{:?} {:?} -> {:?}",
+                    algorithm,
+                    old,
+                    new
+                );
+            }
+        }
+    }
+
+    // Diffs the way the front end did before lines were interned.
+    fn previous_diff(old_text: &str, new_text: &str, options: &DiffOptions) -> Vec<Hunk> {
+        let old_lines = old_text.tokenize_lines();
+        let new_lines = new_text.tokenize_lines();
+        let old_keys: Vec<Cow<str>> = old_lines.iter().map(|l| options.line_key(l)).collect();
+        let new_keys: Vec<Cow<str>> = new_lines.iter().map(|l| options.line_key(l)).collect();
+        let ops =
+            capture_diff_slices_deadline(options.algorithm.into(), &old_keys, &new_keys, None);
+        let tags = line_tags(
+            &ops,
+            options,
+            |i| is_blank(old_lines[i]),
+            |i| is_blank(new_lines[i]),
+        );
+        collect_diffs(tags, &[])
+    }
+
+    #[test]
+    fn same_markers_on_large_files() {
+        let mut lines = Lines(0x9e37_79b9_7f4a_7c15);
+        let old: String = (0..5_000)
+            .map(|i| match lines.next(9) {
+                0 => "}\n".to_string(),
+                1 => "\n".to_string(),
+                2 => "    return;  \r\n".to_string(),
+                _ => format!("    let value = compute({});\n", i % 500),
+            })
+            .collect();
+        let mut new = String::new();
+        for (i, line) in old.lines().enumerate() {
+            match lines.next(100) {
+                0 => continue,
+                1 => new.push_str("    changed();\n"),
+                2 => new.push('\n'),
+                3 => new.push_str(&format!("{}\n", line.trim_end())),
+                _ if i % 250 == 0 => new.push_str(&format!("  {}\n", line)),
+                _ => new.push_str(&format!("{}\n", line)),
+            }
+        }
+        for &algorithm in &[DiffAlgorithm::Myers, DiffAlgorithm::Patience] {
+            for options in &[
+                DiffOptions::default(),
+                DiffOptions {
+                    ignore_blank_lines: true,
+                    ignore_line_endings: true,
+                    ..DiffOptions::default()
+                },
+                DiffOptions {
+                    ignore_whitespace: true,
+                    ..DiffOptions::default()
+                },
+            ] {
+                let options = DiffOptions {
+                    algorithm,
+                    ..*options
+                };
+                assert_eq!(
+                    diff_with_options(&old, &new, &options),
+                    previous_diff(&old, &new, &options),
+                    "{:?}",
+                    options
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn patience_keeps_anchors_outside_the_middle() {
+        // "a" is unique in the changed middle but not in the whole file
+        let old = ["a", "b", "a"];
+        let new = ["a", "a", "b"];
+        assert_eq!(
+            diff_interned(Algorithm::Patience, &old, &new, None, None),
+            capture_diff_slices_deadline(Algorithm::Patience, &old, &new, None)
+        );
+    }
+}
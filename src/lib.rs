@@ -1,23 +1,179 @@
-use similar::{Algorithm, ChangeTag, TextDiff};
+//! Line diffs for rendering an editor's gutter markers.
+//!
+//! With the default `wasm` feature the functions are exported to JS through
+//! wasm-bindgen. Native Rust code can build without it and call
+//! [`diff_lines`], which returns the same markers as [`Hunk`]s.
+
+use std::borrow::Cow;
+use std::hash::Hash;
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use similar::{ChangeTag, DiffOp, DiffTag};
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
-#[wasm_bindgen]
+mod align;
+mod binary;
+mod buffer;
+mod cancel;
+mod clock;
+mod format;
+#[cfg(feature = "git")]
+mod git;
+mod hunks;
+mod inline;
+mod interleaved;
+mod intern;
+mod lines;
+mod merge;
+mod moves;
+mod options;
+mod patch;
+mod revert;
+mod session;
+mod side_by_side;
+mod unified;
+
+use align::align_by_similarity;
+use binary::{diff_bytes_result, diff_result};
+pub use buffer::DiffBuffer;
+use cancel::IsCancelled;
+#[cfg(feature = "wasm")]
+pub use cancel::{line_diff_cancellable, CancellationToken};
+use clock::{deadline_after, Instant};
+pub use format::{decode_line_diff, DecodeError};
+use format::{encode_diffs, encode_legacy_diffs, encode_result};
+#[cfg(feature = "git")]
+pub use git::{diff_working_file, GitBase, GitError};
+#[cfg(feature = "wasm")]
+pub use hunks::diff_hunk_objects;
+pub use hunks::{diff_hunks, DiffHunk, HunkLine, LineTag};
+pub use inline::{inline_diff, InlineGranularity};
+#[cfg(feature = "wasm")]
+pub use interleaved::interleaved_row_objects;
+pub use interleaved::{interleaved_rows, InterleavedRow};
+use intern::diff_interned;
+use lines::{split_byte_lines, split_lines};
+pub use merge::{merge3, ConflictStyle, MergeResult};
+use moves::{detect_moves, weight, with_moves};
+use options::{is_blank, is_blank_bytes};
+pub use options::{DiffAlgorithm, DiffOptions};
+pub use patch::{apply_patch, HunkResult, PatchResult};
+pub use revert::{revert_hunk, stage_hunk};
+pub use session::DiffSession;
+#[cfg(feature = "wasm")]
+pub use side_by_side::side_by_side_row_objects;
+pub use side_by_side::{side_by_side_rows, RowKind, SideBySideRow};
+pub use unified::{unified_diff, unified_diff_with_options};
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub fn line_diff(old_text: &str, new_text: &str) -> Vec<u8> {
+    let result = diff_result(old_text, new_text, &DiffOptions::default(), None);
+    encode_result(&result, false)
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn line_diff_with_options(old_text: &str, new_text: &str, options: &DiffOptions) -> Vec<u8> {
+    let result = diff_result(old_text, new_text, options, None);
+    encode_result(&result, options.compact_encoding)
+}
+
+/// Like `line_diff`, for UTF-8 encoded bytes such as the output of
+/// `TextEncoder` or a file read. Invalid UTF-8 is diffed as is.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn line_diff_bytes(old_bytes: &[u8], new_bytes: &[u8]) -> Vec<u8> {
+    let result = diff_bytes_result(old_bytes, new_bytes, &DiffOptions::default());
+    encode_result(&result, false)
+}
+
+/// Like `line_diff_with_options`, for UTF-8 encoded bytes. Lines that are
+/// not valid UTF-8 only compare equal when they are byte for byte the same.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn line_diff_bytes_with_options(
+    old_bytes: &[u8],
+    new_bytes: &[u8],
+    options: &DiffOptions,
+) -> Vec<u8> {
+    let result = diff_bytes_result(old_bytes, new_bytes, options);
+    encode_result(&result, options.compact_encoding)
+}
+
+/// `line_diff` in the 0.1.7 format: no header and 9 bytes per marker, the
+/// start line, end line and kind byte. Binary content is diffed as text.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn line_diff_legacy(old_text: &str, new_text: &str) -> Vec<u8> {
     let result = diff(old_text, new_text);
+    encode_legacy_diffs(&result)
+}
 
-    // turn sensible struct vec into something that can be passed across
-    // the wasm boundary
-    let mut magic_numbers: std::vec::Vec<u8> = Vec::new();
+/// `line_diff_with_options` in the 0.1.7 format of `line_diff_legacy`,
+/// ignoring `compact_encoding`.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn line_diff_with_options_legacy(
+    old_text: &str,
+    new_text: &str,
+    options: &DiffOptions,
+) -> Vec<u8> {
+    let result = diff_with_options(old_text, new_text, options);
+    encode_legacy_diffs(&result)
+}
 
-    for d in result.iter() {
-        let start_line_bytes = transform_u32_to_array_of_u8(d.start_line);
-        magic_numbers.extend(start_line_bytes);
-        let end_line_bytes = transform_u32_to_array_of_u8(d.end_line);
-        magic_numbers.extend(end_line_bytes);
-        magic_numbers.push(d.kind as u8);
-    }
+#[cfg(feature = "wasm")]
+#[wasm_bindgen(typescript_custom_section)]
+const LINE_DIFF_TS: &'static str = r#"
+export interface LineDiff {
+  startLine: number;
+  endLine: number;
+  kind: DiffKind;
+  oldStartLine: number;
+  oldEndLine: number;
+  deletedLineCount: number;
+}
+
+export interface TextDiff {
+  binary: false;
+  markers: LineDiff[];
+  /** The diff ran into `timeout_ms`, markers may be coarser than needed. */
+  approximate: boolean;
+}
+
+export interface BinaryDiff {
+  binary: true;
+  identical: boolean;
+}
+
+export type DiffResult = TextDiff | BinaryDiff;
+"#;
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "LineDiff[]")]
+    pub type LineDiffArray;
+
+    #[wasm_bindgen(typescript_type = "DiffResult")]
+    pub type DiffResult;
+}
 
-    magic_numbers
+/// Like `line_diff_with_options`, but returns a `TextDiff` with the markers
+/// as `LineDiff` objects instead of the packed byte format, or a
+/// `BinaryDiff` for binary content.
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn line_diff_objects(
+    old_text: &str,
+    new_text: &str,
+    options: &DiffOptions,
+) -> Result<DiffResult, JsValue> {
+    let result = diff_result(old_text, new_text, options, None);
+    Ok(serde_wasm_bindgen::to_value(&result)?.unchecked_into())
+}
+
+// the same markers as `encode_diffs`, as plain JS objects
+#[cfg(feature = "wasm")]
+fn to_js_diffs(result: &[Hunk]) -> Result<LineDiffArray, JsValue> {
+    Ok(serde_wasm_bindgen::to_value(result)?.unchecked_into())
 }
 
 fn transform_u32_to_array_of_u8(x: u32) -> [u8; 4] {
@@ -25,96 +181,488 @@ fn transform_u32_to_array_of_u8(x: u32) -> [u8; 4] {
     let b2: u8 = ((x >> 16) & 0xff) as u8;
     let b3: u8 = ((x >> 8) & 0xff) as u8;
     let b4: u8 = (x & 0xff) as u8;
-    return [b1, b2, b3, b4];
+    [b1, b2, b3, b4]
 }
 
+/// The kind of a marker, also the kind byte of the packed format.
+#[cfg_attr(feature = "wasm", wasm_bindgen(js_name = DiffKind))]
 #[derive(Debug, PartialEq, Copy, Clone)]
-enum DiffKind {
+pub enum HunkKind {
     Add = 1,
     Delete = 2,
     Modify = 3,
+    Conflict = 4,
+    /// A caret where a block of lines was moved away from, with
+    /// `DiffOptions::detect_moves`. The old lines are the moved lines.
+    MovedFrom = 5,
+    /// The lines a block was moved to. The old lines are where it came
+    /// from, the same as for its `MovedFrom` caret.
+    MovedTo = 6,
+}
+
+// JS objects get the same number as the kind byte, which is what the
+// generated `DiffKind` enum compares equal to
+impl Serialize for HunkKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+/// A gutter marker, as encoded by `line_diff`.
+///
+/// `start_line`/`end_line` are in the new document. `old_start_line` and
+/// `old_end_line` are the matching lines of the old document, for an add that
+/// is the single old line the insert sits in front of, mirroring how a delete
+/// caret sits in front of a new line. `deleted_line_count` is how many old
+/// lines a delete caret or modify stands for; carets merged across an equal
+/// line span more old lines than they deleted.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hunk {
+    pub start_line: u32,
+    pub end_line: u32,
+    pub kind: HunkKind,
+    pub old_start_line: u32,
+    pub old_end_line: u32,
+    pub deleted_line_count: u32,
 }
 
+/// What `line_diff` found. Binary content is not diffed line by line, only
+/// compared.
 #[derive(Debug, PartialEq)]
-struct Diff {
-    start_line: u32,
-    end_line: u32,
-    kind: DiffKind,
+pub enum LineDiffResult {
+    /// `approximate` is set when the diff ran into `DiffOptions::timeout_ms`.
+    Text {
+        markers: Vec<Hunk>,
+        approximate: bool,
+    },
+    Binary {
+        identical: bool,
+    },
+}
+
+// `TextDiff` and `BinaryDiff` objects
+impl Serialize for LineDiffResult {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            LineDiffResult::Text {
+                markers,
+                approximate,
+            } => {
+                let mut text = serializer.serialize_struct("TextDiff", 3)?;
+                text.serialize_field("binary", &false)?;
+                text.serialize_field("markers", markers)?;
+                text.serialize_field("approximate", approximate)?;
+                text.end()
+            }
+            LineDiffResult::Binary { identical } => {
+                let mut binary = serializer.serialize_struct("BinaryDiff", 2)?;
+                binary.serialize_field("binary", &true)?;
+                binary.serialize_field("identical", identical)?;
+                binary.end()
+            }
+        }
+    }
 }
 
-fn diff(old_text: &str, new_text: &str) -> Vec<Diff> {
-    let diff = TextDiff::configure()
-        .algorithm(Algorithm::Patience)
-        .diff_lines(old_text, new_text);
+/// Diffs two texts line by line into the gutter markers `line_diff` encodes,
+/// for use from Rust.
+///
+/// Binary content is diffed as text, use `decode_line_diff(&line_diff(..))`
+/// to tell it apart.
+///
+/// ```
+/// use line_diff_wasm::{diff_lines, DiffOptions, Hunk, HunkKind};
+///
+/// let hunks = diff_lines("a\nb\nc\n", "a\nB\nc\n", &DiffOptions::default());
+/// assert_eq!(
+///     hunks,
+///     vec![Hunk {
+///         start_line: 2,
+///         end_line: 2,
+///         kind: HunkKind::Modify,
+///         old_start_line: 2,
+///         old_end_line: 2,
+///         deleted_line_count: 1,
+///     }]
+/// );
+/// ```
+pub fn diff_lines(old_text: &str, new_text: &str, options: &DiffOptions) -> Vec<Hunk> {
+    diff_with_options(old_text, new_text, options)
+}
+
+fn diff(old_text: &str, new_text: &str) -> Vec<Hunk> {
+    diff_with_options(old_text, new_text, &DiffOptions::default())
+}
+
+fn diff_with_options(old_text: &str, new_text: &str, options: &DiffOptions) -> Vec<Hunk> {
+    let deadline = deadline_after(options.timeout_ms);
+    diff_with_deadline(old_text, new_text, options, deadline, None)
+}
+
+fn diff_with_deadline(
+    old_text: &str,
+    new_text: &str,
+    options: &DiffOptions,
+    deadline: Option<Instant>,
+    cancelled: Option<IsCancelled>,
+) -> Vec<Hunk> {
+    let old_lines = split_lines(old_text);
+    let new_lines = split_lines(new_text);
+    text_changes(&old_lines, &new_lines, options, deadline, cancelled).markers()
+}
+
+fn text_changes(
+    old_lines: &[&str],
+    new_lines: &[&str],
+    options: &DiffOptions,
+    deadline: Option<Instant>,
+    cancelled: Option<IsCancelled>,
+) -> LineChanges {
+    let old_keys = line_keys(old_lines, options);
+    let new_keys = line_keys(new_lines, options);
+    let tags = line_tags(
+        &diff_keys(&old_keys, &new_keys, options, deadline, cancelled),
+        options,
+        |i| is_blank(old_lines[i]),
+        |i| is_blank(new_lines[i]),
+    );
+    line_changes(
+        tags,
+        options,
+        deadline,
+        &old_keys,
+        &new_keys,
+        |i| old_lines[i].as_bytes(),
+        |i| new_lines[i].as_bytes(),
+    )
+}
+
+fn diff_bytes_with_options(
+    old_bytes: &[u8],
+    new_bytes: &[u8],
+    options: &DiffOptions,
+    deadline: Option<Instant>,
+) -> Vec<Hunk> {
+    let old_lines = split_byte_lines(old_bytes);
+    let new_lines = split_byte_lines(new_bytes);
+    let old_keys: Vec<Cow<[u8]>> = old_lines
+        .iter()
+        .map(|line| options.byte_line_key(line))
+        .collect();
+    let new_keys: Vec<Cow<[u8]>> = new_lines
+        .iter()
+        .map(|line| options.byte_line_key(line))
+        .collect();
+    let tags = line_tags(
+        &diff_keys(&old_keys, &new_keys, options, deadline, None),
+        options,
+        |i| is_blank_bytes(old_lines[i]),
+        |i| is_blank_bytes(new_lines[i]),
+    );
+    collect_markers(
+        tags,
+        options,
+        deadline,
+        &old_keys,
+        &new_keys,
+        |i| old_lines[i],
+        |i| new_lines[i],
+    )
+}
+
+fn diff_line_tags(
+    old_lines: &[&str],
+    new_lines: &[&str],
+    options: &DiffOptions,
+    deadline: Option<Instant>,
+    cancelled: Option<IsCancelled>,
+) -> Vec<(ChangeTag, bool)> {
+    line_tags(
+        &diff_line_ops(old_lines, new_lines, options, deadline, cancelled),
+        options,
+        |i| is_blank(old_lines[i]),
+        |i| is_blank(new_lines[i]),
+    )
+}
 
+fn diff_line_ops(
+    old_lines: &[&str],
+    new_lines: &[&str],
+    options: &DiffOptions,
+    deadline: Option<Instant>,
+    cancelled: Option<IsCancelled>,
+) -> Vec<DiffOp> {
+    let old_keys = line_keys(old_lines, options);
+    let new_keys = line_keys(new_lines, options);
+    diff_keys(&old_keys, &new_keys, options, deadline, cancelled)
+}
+
+// Normalized lines to diff, which map 1:1 onto the real ones.
+fn line_keys<'a>(lines: &[&'a str], options: &DiffOptions) -> Vec<Cow<'a, str>> {
+    lines.iter().map(|line| options.line_key(line)).collect()
+}
+
+// Past the deadline similar stops looking for the best alignment and falls
+// back to replacing whatever is left, which is coarse but still correct.
+fn diff_keys<K: Hash + Eq>(
+    old_keys: &[K],
+    new_keys: &[K],
+    options: &DiffOptions,
+    deadline: Option<Instant>,
+    cancelled: Option<IsCancelled>,
+) -> Vec<DiffOp> {
+    diff_interned(
+        options.algorithm.into(),
+        old_keys,
+        new_keys,
+        deadline,
+        cancelled,
+    )
+}
+
+// Expands diff ops into per-line change tags, paired with whether the change
+// is ignored. With `ignore_blank_lines`, changes made up only of blank lines
+// are ignored: they still count towards line numbers but never become
+// markers.
+fn line_tags(
+    ops: &[DiffOp],
+    options: &DiffOptions,
+    old_blank: impl Fn(usize) -> bool,
+    new_blank: impl Fn(usize) -> bool,
+) -> Vec<(ChangeTag, bool)> {
+    if !options.ignore_blank_lines {
+        return op_tags(ops).map(|tag| (tag, false)).collect();
+    }
+
+    let is_equal = |op: &DiffOp| matches!(op, DiffOp::Equal { .. });
+    let mut tags = Vec::new();
+    for run in ops.chunk_by(|a, b| is_equal(a) == is_equal(b)) {
+        let only_blank = run.iter().all(|op| {
+            !is_equal(op) && op.old_range().all(&old_blank) && op.new_range().all(&new_blank)
+        });
+        tags.extend(op_tags(run).map(|tag| (tag, only_blank)));
+    }
+    tags
+}
+
+// Expands diff ops into the per-line change tags `iter_all_changes` would
+// yield.
+fn op_tags(ops: &[DiffOp]) -> impl Iterator<Item = ChangeTag> + '_ {
+    ops.iter().flat_map(|op| {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        let (deletes, inserts, equals) = match tag {
+            DiffTag::Equal => (0, 0, new_range.len()),
+            DiffTag::Delete => (old_range.len(), 0, 0),
+            DiffTag::Insert => (0, new_range.len(), 0),
+            DiffTag::Replace => (old_range.len(), new_range.len(), 0),
+        };
+        std::iter::repeat_n(ChangeTag::Delete, deletes)
+            .chain(std::iter::repeat_n(ChangeTag::Insert, inserts))
+            .chain(std::iter::repeat_n(ChangeTag::Equal, equals))
+    })
+}
+
+// The change tags with what the options ask for on top: blocks of lines that
+// moved are ignored and reported as `MovedFrom` and `MovedTo` markers instead,
+// and deleted and inserted lines are reordered and split by `breaks` so they
+// are paired into modifies by similarity rather than in order.
+struct LineChanges {
+    tags: Vec<(ChangeTag, bool)>,
+    breaks: Vec<usize>,
+    moves: Vec<Hunk>,
+}
+
+impl LineChanges {
+    fn markers(self) -> Vec<Hunk> {
+        with_moves(collect_diffs(self.tags, &self.breaks), self.moves)
+    }
+}
+
+fn collect_markers<'a, K: Hash + Eq>(
+    tags: Vec<(ChangeTag, bool)>,
+    options: &DiffOptions,
+    deadline: Option<Instant>,
+    old_keys: &[K],
+    new_keys: &[K],
+    old_line: impl Fn(usize) -> &'a [u8],
+    new_line: impl Fn(usize) -> &'a [u8],
+) -> Vec<Hunk> {
+    line_changes(
+        tags, options, deadline, old_keys, new_keys, old_line, new_line,
+    )
+    .markers()
+}
+
+fn line_changes<'a, K: Hash + Eq>(
+    mut tags: Vec<(ChangeTag, bool)>,
+    options: &DiffOptions,
+    deadline: Option<Instant>,
+    old_keys: &[K],
+    new_keys: &[K],
+    old_line: impl Fn(usize) -> &'a [u8],
+    new_line: impl Fn(usize) -> &'a [u8],
+) -> LineChanges {
+    let moves = if options.detect_moves {
+        detect_moves(&mut tags, old_keys, new_keys, |i| weight(new_line(i)))
+    } else {
+        Vec::new()
+    };
+    let breaks = if options.similarity_threshold > 0.0 {
+        align_by_similarity(
+            &mut tags,
+            options.similarity_threshold,
+            deadline,
+            old_line,
+            new_line,
+        )
+    } else {
+        Vec::new()
+    };
+    LineChanges {
+        tags,
+        breaks,
+        moves,
+    }
+}
+
+// `breaks` are the sorted tag indices in front of which pending deletes become
+// a delete caret instead of being paired with the inserts that follow.
+fn collect_diffs<I: IntoIterator<Item = (ChangeTag, bool)>>(
+    tags: I,
+    breaks: &[usize],
+) -> Vec<Hunk> {
     let mut line_new_text = 1;
+    let mut line_old_text = 1;
+    let mut active_delete_start_line = 0;
     let mut active_delete_line_count = 0;
 
-    let mut diff_vec: std::vec::Vec<Diff> = Vec::new();
+    let mut diff_vec: std::vec::Vec<Hunk> = Vec::new();
 
     // Process all changes into "new document" line numbers
     // with deletes collapsed into single "carats" and delete/inserts collapsed into "modifes"
-    for change in diff.iter_all_changes() {
-        if matches!(change.tag(), ChangeTag::Equal) && active_delete_line_count > 0 {
+    let mut breaks = breaks.iter().peekable();
+    for (index, (tag, ignored)) in tags.into_iter().enumerate() {
+        let at_break = breaks.next_if_eq(&&index).is_some();
+        if at_break && active_delete_line_count > 0 {
+            diff_vec.push(Hunk {
+                start_line: line_new_text,
+                end_line: line_new_text,
+                kind: HunkKind::Delete,
+                old_start_line: active_delete_start_line,
+                old_end_line: active_delete_start_line + active_delete_line_count - 1,
+                deleted_line_count: active_delete_line_count,
+            });
             active_delete_line_count = 0;
-            diff_vec.push(Diff {
+        }
+        if ignored {
+            // the old lines of a caret have to be contiguous
+            if matches!(tag, ChangeTag::Delete) && active_delete_line_count > 0 {
+                diff_vec.push(Hunk {
+                    start_line: line_new_text,
+                    end_line: line_new_text,
+                    kind: HunkKind::Delete,
+                    old_start_line: active_delete_start_line,
+                    old_end_line: active_delete_start_line + active_delete_line_count - 1,
+                    deleted_line_count: active_delete_line_count,
+                });
+                active_delete_line_count = 0;
+            }
+            match tag {
+                ChangeTag::Equal => {
+                    line_new_text += 1;
+                    line_old_text += 1;
+                }
+                ChangeTag::Delete => line_old_text += 1,
+                ChangeTag::Insert => line_new_text += 1,
+            }
+            continue;
+        }
+
+        if matches!(tag, ChangeTag::Equal) && active_delete_line_count > 0 {
+            diff_vec.push(Hunk {
                 start_line: line_new_text,
                 end_line: line_new_text,
-                kind: DiffKind::Delete,
-            })
+                kind: HunkKind::Delete,
+                old_start_line: active_delete_start_line,
+                old_end_line: active_delete_start_line + active_delete_line_count - 1,
+                deleted_line_count: active_delete_line_count,
+            });
+            active_delete_line_count = 0;
         }
 
-        if matches!(change.tag(), ChangeTag::Delete) {
+        if matches!(tag, ChangeTag::Delete) {
+            if active_delete_line_count == 0 {
+                active_delete_start_line = line_old_text;
+            }
             active_delete_line_count += 1;
         }
 
-        if matches!(change.tag(), ChangeTag::Insert) {
+        if matches!(tag, ChangeTag::Insert) {
             if active_delete_line_count > 0 {
-                active_delete_line_count -= 1;
-                diff_vec.push(Diff {
+                diff_vec.push(Hunk {
                     start_line: line_new_text,
                     end_line: line_new_text,
-                    kind: DiffKind::Modify,
+                    kind: HunkKind::Modify,
+                    old_start_line: active_delete_start_line,
+                    old_end_line: active_delete_start_line,
+                    deleted_line_count: 1,
                 });
+                active_delete_start_line += 1;
+                active_delete_line_count -= 1;
             } else {
-                diff_vec.push(Diff {
+                diff_vec.push(Hunk {
                     start_line: line_new_text,
                     end_line: line_new_text,
-                    kind: DiffKind::Add,
+                    kind: HunkKind::Add,
+                    old_start_line: line_old_text,
+                    old_end_line: line_old_text,
+                    deleted_line_count: 0,
                 });
             }
         }
 
-        if matches!(change.tag(), ChangeTag::Equal) || matches!(change.tag(), ChangeTag::Insert) {
+        if matches!(tag, ChangeTag::Equal) || matches!(tag, ChangeTag::Insert) {
             line_new_text += 1;
         }
+        if matches!(tag, ChangeTag::Equal) || matches!(tag, ChangeTag::Delete) {
+            line_old_text += 1;
+        }
     }
 
     if active_delete_line_count > 0 {
-        diff_vec.push(Diff {
+        diff_vec.push(Hunk {
             start_line: line_new_text,
             end_line: line_new_text,
-            kind: DiffKind::Delete,
+            kind: HunkKind::Delete,
+            old_start_line: active_delete_start_line,
+            old_end_line: active_delete_start_line + active_delete_line_count - 1,
+            deleted_line_count: active_delete_line_count,
         })
     }
 
     // horrible way to collapse adjacent changes into multi-line bars
     // xxx: surely a more idiomatic rust way, or an in-place algorithm?
-    let mut merged_vec: std::vec::Vec<Diff> = Vec::new();
+    let mut merged_vec: std::vec::Vec<Hunk> = Vec::new();
 
     let mut skip = 0;
     for i in 0..diff_vec.len() {
         if i < skip {
             continue;
         }
-        let mut current = Diff {
+        let mut current = Hunk {
             start_line: diff_vec[i].start_line,
             end_line: diff_vec[i].end_line,
             kind: diff_vec[i].kind,
+            old_start_line: diff_vec[i].old_start_line,
+            old_end_line: diff_vec[i].old_end_line,
+            deleted_line_count: diff_vec[i].deleted_line_count,
         };
-        for j in i + 1..diff_vec.len() {
-            if diff_vec[j].kind == current.kind && diff_vec[j].start_line == current.end_line + 1 {
-                current.end_line = diff_vec[j].end_line;
+        for next in diff_vec.iter().skip(i + 1) {
+            if next.kind == current.kind && next.start_line == current.end_line + 1 {
+                current.end_line = next.end_line;
+                current.old_end_line = next.old_end_line;
+                current.deleted_line_count += next.deleted_line_count;
                 skip += 1;
             } else {
                 break;
@@ -129,12 +677,24 @@ fn diff(old_text: &str, new_text: &str) -> Vec<Diff> {
 
 #[cfg(test)]
 mod tests {
+    use crate::clock::Instant;
+    use crate::decode_line_diff;
     use crate::diff;
+    use crate::diff_bytes_with_options;
+    use crate::diff_with_deadline;
+    use crate::diff_with_options;
     use crate::line_diff;
-    use crate::Diff;
-    use crate::DiffKind;
+    use crate::line_diff_bytes;
+    use crate::line_diff_legacy;
+    use crate::line_diff_with_options;
+    use crate::line_diff_with_options_legacy;
+    use crate::DiffAlgorithm;
+    use crate::DiffOptions;
+    use crate::Hunk;
+    use crate::HunkKind;
+    use crate::LineDiffResult;
 
-    fn vec_compare(va: std::vec::Vec<Diff>, vb: std::vec::Vec<Diff>) -> bool {
+    fn vec_compare(va: std::vec::Vec<Hunk>, vb: std::vec::Vec<Hunk>) -> bool {
         (va.len() == vb.len()) &&  // zip stops at the shortest
          va.iter()
            .zip(vb)
@@ -145,98 +705,139 @@ mod tests {
     fn no_changes() {
         let out = diff("hello, world\n2\n3\n4\n", "hello, world\n2\n3\n4\n");
         let expected = vec![];
-        assert_eq!(vec_compare(out, expected), true);
+        assert!(vec_compare(out, expected));
     }
 
     #[test]
     fn single_add() {
         let out = diff("", "hello, world\n");
-        let expected = vec![Diff {
-            kind: DiffKind::Add,
+        let expected = vec![Hunk {
+            kind: HunkKind::Add,
             start_line: 1,
             end_line: 1,
+            old_start_line: 1,
+            old_end_line: 1,
+            deleted_line_count: 0,
         }];
-        assert_eq!(vec_compare(out, expected), true);
+        assert!(vec_compare(out, expected));
     }
 
     #[test]
     fn single_delete() {
         let out = diff("hello, world\n", "");
-        let expected = vec![Diff {
-            kind: DiffKind::Delete,
+        let expected = vec![Hunk {
+            kind: HunkKind::Delete,
             start_line: 1,
             end_line: 1,
+            old_start_line: 1,
+            old_end_line: 1,
+            deleted_line_count: 1,
         }];
-        assert_eq!(vec_compare(out, expected), true);
+        assert!(vec_compare(out, expected));
     }
 
     #[test]
     fn single_modify() {
         let out = diff("hello, world\n", "hello, test\n");
-        let expected = vec![Diff {
-            kind: DiffKind::Modify,
+        let expected = vec![Hunk {
+            kind: HunkKind::Modify,
             start_line: 1,
             end_line: 1,
+            old_start_line: 1,
+            old_end_line: 1,
+            deleted_line_count: 1,
         }];
-        assert_eq!(vec_compare(out, expected), true);
+        assert!(vec_compare(out, expected));
     }
 
     #[test]
     fn modify_and_add() {
         let out = diff("hello, world\n", "hello, test\na\nb\n");
         let expected = vec![
-            Diff {
-                kind: DiffKind::Modify,
+            Hunk {
+                kind: HunkKind::Modify,
                 start_line: 1,
                 end_line: 1,
+                old_start_line: 1,
+                old_end_line: 1,
+                deleted_line_count: 1,
             },
-            Diff {
-                kind: DiffKind::Add,
+            Hunk {
+                kind: HunkKind::Add,
                 start_line: 2,
                 end_line: 3,
+                old_start_line: 2,
+                old_end_line: 2,
+                deleted_line_count: 0,
             },
         ];
-        assert_eq!(vec_compare(out, expected), true);
+        assert!(vec_compare(out, expected));
     }
 
     #[test]
     fn modify_and_delete() {
         let out = diff("hello, world\na\nb\n", "hello, test\n");
         let expected = vec![
-            Diff {
-                kind: DiffKind::Modify,
+            Hunk {
+                kind: HunkKind::Modify,
                 start_line: 1,
                 end_line: 1,
+                old_start_line: 1,
+                old_end_line: 1,
+                deleted_line_count: 1,
             },
-            Diff {
-                kind: DiffKind::Delete,
+            Hunk {
+                kind: HunkKind::Delete,
                 start_line: 2,
                 end_line: 2,
+                old_start_line: 2,
+                old_end_line: 3,
+                deleted_line_count: 2,
             },
         ];
-        assert_eq!(vec_compare(out, expected), true);
+        assert!(vec_compare(out, expected));
     }
 
     #[test]
     fn prefix_add() {
         let out = diff("hello, world\n", "a\nhello, world\n");
-        let expected = vec![Diff {
-            kind: DiffKind::Add,
+        let expected = vec![Hunk {
+            kind: HunkKind::Add,
             start_line: 1,
             end_line: 1,
+            old_start_line: 1,
+            old_end_line: 1,
+            deleted_line_count: 0,
         }];
-        assert_eq!(vec_compare(out, expected), true);
+        assert!(vec_compare(out, expected));
     }
 
     #[test]
     fn prefix_delete() {
         let out = diff("a\nhello, world\n", "hello, world\n");
-        let expected = vec![Diff {
-            kind: DiffKind::Delete,
+        let expected = vec![Hunk {
+            kind: HunkKind::Delete,
             start_line: 1,
             end_line: 1,
+            old_start_line: 1,
+            old_end_line: 1,
+            deleted_line_count: 1,
         }];
-        assert_eq!(vec_compare(out, expected), true);
+        assert!(vec_compare(out, expected));
+    }
+
+    #[test]
+    fn merged_delete_carets() {
+        let out = diff("a\nx\nb\ny\nc\n", "a\nb\nc\n");
+        let expected = vec![Hunk {
+            kind: HunkKind::Delete,
+            start_line: 2,
+            end_line: 3,
+            old_start_line: 2,
+            old_end_line: 4,
+            deleted_line_count: 2,
+        }];
+        assert!(vec_compare(out, expected));
     }
 
     #[test]
@@ -267,28 +868,359 @@ mod tests {
 "#;
         let out = diff(before, after);
         let expected = vec![
-            Diff {
-                kind: DiffKind::Modify,
+            Hunk {
+                kind: HunkKind::Modify,
                 start_line: 2,
                 end_line: 2,
+                old_start_line: 2,
+                old_end_line: 2,
+                deleted_line_count: 1,
             },
-            Diff {
-                kind: DiffKind::Delete,
+            Hunk {
+                kind: HunkKind::Delete,
                 start_line: 5,
                 end_line: 5,
+                old_start_line: 5,
+                old_end_line: 5,
+                deleted_line_count: 1,
             },
-            Diff {
-                kind: DiffKind::Add,
+            Hunk {
+                kind: HunkKind::Add,
                 start_line: 8,
                 end_line: 9,
+                old_start_line: 9,
+                old_end_line: 9,
+                deleted_line_count: 0,
             },
-            Diff {
-                kind: DiffKind::Modify,
+            Hunk {
+                kind: HunkKind::Modify,
                 start_line: 11,
                 end_line: 11,
+                old_start_line: 10,
+                old_end_line: 10,
+                deleted_line_count: 1,
+            },
+        ];
+        // both modified lines are similar enough to stay modifies
+        let options = DiffOptions {
+            similarity_threshold: 0.5,
+            ..DiffOptions::default()
+        };
+        assert_eq!(diff_with_options(before, after, &options), expected);
+        assert!(vec_compare(out, expected));
+    }
+
+    #[test]
+    fn unrelated_lines_are_not_modifies() {
+        let before = "a\nfoo bar baz\nqux quux\nlorem ipsum\nz\n";
+        let after = "a\n1234\n5678\n9012\nz\n";
+        let options = DiffOptions {
+            similarity_threshold: 0.5,
+            ..DiffOptions::default()
+        };
+        assert_eq!(
+            diff_with_options(before, after, &options),
+            vec![
+                Hunk {
+                    kind: HunkKind::Delete,
+                    start_line: 2,
+                    end_line: 2,
+                    old_start_line: 2,
+                    old_end_line: 4,
+                    deleted_line_count: 3,
+                },
+                Hunk {
+                    kind: HunkKind::Add,
+                    start_line: 2,
+                    end_line: 4,
+                    old_start_line: 5,
+                    old_end_line: 5,
+                    deleted_line_count: 0,
+                },
+            ]
+        );
+        // paired in order without a threshold
+        assert!(diff(before, after)
+            .iter()
+            .all(|hunk| hunk.kind == HunkKind::Modify));
+    }
+
+    #[test]
+    fn edit_next_to_inserted_line() {
+        let before = "fn f() {\n    let a = 1;\n    call();\n}\n";
+        let after = "fn f() {\n    let z = 0;\n    let a = 2;\n    call();\n}\n";
+        let options = DiffOptions {
+            similarity_threshold: 0.5,
+            ..DiffOptions::default()
+        };
+        let kinds = |hunks: Vec<Hunk>| -> Vec<(HunkKind, u32)> {
+            hunks
+                .iter()
+                .map(|hunk| (hunk.kind, hunk.start_line))
+                .collect()
+        };
+        assert_eq!(
+            kinds(diff(before, after)),
+            vec![(HunkKind::Modify, 2), (HunkKind::Add, 3)]
+        );
+        assert_eq!(
+            kinds(diff_with_options(before, after, &options)),
+            vec![(HunkKind::Add, 2), (HunkKind::Modify, 3)]
+        );
+        assert_eq!(
+            diff_with_options(before, after, &options)[1],
+            Hunk {
+                kind: HunkKind::Modify,
+                start_line: 3,
+                end_line: 3,
+                old_start_line: 2,
+                old_end_line: 2,
+                deleted_line_count: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn similarity_with_deletes_between_pairs() {
+        let before = "x\nalpha = 1\nremoved line\nbeta = 2\ny\n";
+        let after = "x\nalpha = 10\nbeta = 20\ny\n";
+        let options = DiffOptions {
+            similarity_threshold: 0.5,
+            ..DiffOptions::default()
+        };
+        assert_eq!(
+            diff_with_options(before, after, &options),
+            vec![
+                Hunk {
+                    kind: HunkKind::Modify,
+                    start_line: 2,
+                    end_line: 2,
+                    old_start_line: 2,
+                    old_end_line: 2,
+                    deleted_line_count: 1,
+                },
+                Hunk {
+                    kind: HunkKind::Delete,
+                    start_line: 3,
+                    end_line: 3,
+                    old_start_line: 3,
+                    old_end_line: 3,
+                    deleted_line_count: 1,
+                },
+                Hunk {
+                    kind: HunkKind::Modify,
+                    start_line: 3,
+                    end_line: 3,
+                    old_start_line: 4,
+                    old_end_line: 4,
+                    deleted_line_count: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn algorithms_differ() {
+        let before = "c\nb\nb\n";
+        let after = "b\nc\nb\n";
+        let with = |algorithm| {
+            diff_with_options(
+                before,
+                after,
+                &DiffOptions {
+                    algorithm,
+                    ..DiffOptions::default()
+                },
+            )
+        };
+
+        let myers = vec![
+            Hunk {
+                kind: HunkKind::Add,
+                start_line: 1,
+                end_line: 1,
+                old_start_line: 1,
+                old_end_line: 1,
+                deleted_line_count: 0,
+            },
+            Hunk {
+                kind: HunkKind::Delete,
+                start_line: 3,
+                end_line: 3,
+                old_start_line: 2,
+                old_end_line: 2,
+                deleted_line_count: 1,
+            },
+        ];
+        assert!(vec_compare(with(DiffAlgorithm::Myers), myers));
+
+        let patience = vec![
+            Hunk {
+                kind: HunkKind::Add,
+                start_line: 1,
+                end_line: 1,
+                old_start_line: 1,
+                old_end_line: 1,
+                deleted_line_count: 0,
+            },
+            Hunk {
+                kind: HunkKind::Delete,
+                start_line: 4,
+                end_line: 4,
+                old_start_line: 3,
+                old_end_line: 3,
+                deleted_line_count: 1,
+            },
+        ];
+        assert!(vec_compare(with(DiffAlgorithm::Patience), patience));
+        assert!(vec_compare(
+            diff(before, after),
+            with(DiffAlgorithm::Patience)
+        ));
+
+        let lcs = vec![
+            Hunk {
+                kind: HunkKind::Delete,
+                start_line: 1,
+                end_line: 1,
+                old_start_line: 1,
+                old_end_line: 1,
+                deleted_line_count: 1,
+            },
+            Hunk {
+                kind: HunkKind::Add,
+                start_line: 2,
+                end_line: 2,
+                old_start_line: 3,
+                old_end_line: 3,
+                deleted_line_count: 0,
             },
         ];
-        assert_eq!(vec_compare(out, expected), true);
+        assert!(vec_compare(with(DiffAlgorithm::Lcs), lcs));
+    }
+
+    #[test]
+    fn lcs_overlapping_prefix_and_suffix() {
+        let out = diff_with_options(
+            "a\na\n",
+            "a\n",
+            &DiffOptions {
+                algorithm: DiffAlgorithm::Lcs,
+                ..DiffOptions::default()
+            },
+        );
+        let expected = vec![Hunk {
+            kind: HunkKind::Delete,
+            start_line: 2,
+            end_line: 2,
+            old_start_line: 2,
+            old_end_line: 2,
+            deleted_line_count: 1,
+        }];
+        assert!(vec_compare(out, expected));
+    }
+
+    #[test]
+    fn ignore_line_endings() {
+        let before = "a\r\nb\r\nc\r\n";
+        let after = "a\nb\nC\n";
+        let options = DiffOptions {
+            ignore_line_endings: true,
+            ..DiffOptions::default()
+        };
+        let out = diff_with_options(before, after, &options);
+        let expected = vec![Hunk {
+            kind: HunkKind::Modify,
+            start_line: 3,
+            end_line: 3,
+            old_start_line: 3,
+            old_end_line: 3,
+            deleted_line_count: 1,
+        }];
+        assert!(vec_compare(out, expected));
+
+        let out = diff(before, after);
+        let expected = vec![Hunk {
+            kind: HunkKind::Modify,
+            start_line: 1,
+            end_line: 3,
+            old_start_line: 1,
+            old_end_line: 3,
+            deleted_line_count: 3,
+        }];
+        assert!(vec_compare(out, expected));
+    }
+
+    #[test]
+    fn ignore_trailing_whitespace() {
+        let before = "a\nb\n  c\n";
+        let after = "a  \nb\t\nc\n";
+        let options = DiffOptions {
+            ignore_trailing_whitespace: true,
+            ..DiffOptions::default()
+        };
+        let out = diff_with_options(before, after, &options);
+        let expected = vec![Hunk {
+            kind: HunkKind::Modify,
+            start_line: 3,
+            end_line: 3,
+            old_start_line: 3,
+            old_end_line: 3,
+            deleted_line_count: 1,
+        }];
+        assert!(vec_compare(out, expected));
+    }
+
+    #[test]
+    fn ignore_whitespace() {
+        let before = "fn main() {\n    let x = 1;\n}\n";
+        let after = "fn main(){\n\tlet x=1;\n}\nmore\n";
+        let options = DiffOptions {
+            ignore_whitespace: true,
+            ..DiffOptions::default()
+        };
+        let out = diff_with_options(before, after, &options);
+        let expected = vec![Hunk {
+            kind: HunkKind::Add,
+            start_line: 4,
+            end_line: 4,
+            old_start_line: 4,
+            old_end_line: 4,
+            deleted_line_count: 0,
+        }];
+        assert!(vec_compare(out, expected));
+    }
+
+    #[test]
+    fn ignore_blank_lines() {
+        let before = "a\nb\n\nc\n";
+        let after = "a\n\nb\n\n  \nc\nd\n";
+        let options = DiffOptions {
+            ignore_blank_lines: true,
+            ..DiffOptions::default()
+        };
+        let out = diff_with_options(before, after, &options);
+        let expected = vec![Hunk {
+            kind: HunkKind::Add,
+            start_line: 7,
+            end_line: 7,
+            old_start_line: 5,
+            old_end_line: 5,
+            deleted_line_count: 0,
+        }];
+        assert!(vec_compare(out, expected));
+
+        // a blank line replacing a non-blank one is still a change
+        let out = diff_with_options("a\nb\nc\n", "a\n\nc\n", &options);
+        let expected = vec![Hunk {
+            kind: HunkKind::Modify,
+            start_line: 2,
+            end_line: 2,
+            old_start_line: 2,
+            old_end_line: 2,
+            deleted_line_count: 1,
+        }];
+        assert!(vec_compare(out, expected));
     }
 
     // xxx: generic instead
@@ -299,24 +1231,172 @@ mod tests {
            .all(|(a,b)| *a == b)
     }
 
+    #[test]
+    fn deadline_falls_back_to_coarse_diff() {
+        let options = DiffOptions {
+            algorithm: DiffAlgorithm::Myers,
+            ..DiffOptions::default()
+        };
+        let out = diff_with_deadline(
+            "a\nb\nc\nd\n",
+            "a\nB\nc\nD\n",
+            &options,
+            Some(Instant::now()),
+            None,
+        );
+        let expected = vec![Hunk {
+            kind: HunkKind::Modify,
+            start_line: 2,
+            end_line: 4,
+            old_start_line: 2,
+            old_end_line: 4,
+            deleted_line_count: 3,
+        }];
+        assert!(vec_compare(out, expected));
+    }
+
+    #[test]
+    fn bytes_match_text() {
+        let before = "a\nb \nc\r\nd\n";
+        let after = "a\nb\nc\ne\n";
+        let options = DiffOptions {
+            ignore_line_endings: true,
+            ignore_trailing_whitespace: true,
+            ..DiffOptions::default()
+        };
+        assert_eq!(
+            diff_bytes_with_options(before.as_bytes(), after.as_bytes(), &options, None),
+            diff_with_options(before, after, &options)
+        );
+        assert_eq!(
+            line_diff_bytes(before.as_bytes(), after.as_bytes()),
+            line_diff(before, after)
+        );
+    }
+
+    #[test]
+    fn invalid_utf8_bytes() {
+        let out = diff_bytes_with_options(
+            b"a\n\xff\xfe\nc\n\xc3\n",
+            b"a\n\xff\xfd\nc\n\xc3\n",
+            &DiffOptions::default(),
+            None,
+        );
+        let expected = vec![Hunk {
+            kind: HunkKind::Modify,
+            start_line: 2,
+            end_line: 2,
+            old_start_line: 2,
+            old_end_line: 2,
+            deleted_line_count: 1,
+        }];
+        assert!(vec_compare(out, expected));
+    }
+
     #[test]
     fn wasm_empty() {
-        let out = line_diff("hello, world\n2\n3\n4\n", "hello, world\n2\n3\n4\n");
+        let out = line_diff_legacy("hello, world\n2\n3\n4\n", "hello, world\n2\n3\n4\n");
         let expected = vec![];
-        assert_eq!(u8_vec_compare(out, expected), true);
+        assert!(u8_vec_compare(out, expected));
     }
 
     #[test]
     fn wasm_modify_and_delete() {
-        let out = line_diff("hello, world\na\nb\n", "hello, test\n");
+        let out = line_diff_legacy("hello, world\na\nb\n", "hello, test\n");
         let expected = vec![0, 0, 0, 1, 0, 0, 0, 1, 3, 0, 0, 0, 2, 0, 0, 0, 2, 2];
-        assert_eq!(u8_vec_compare(out, expected), true);
+        assert!(u8_vec_compare(out, expected));
+    }
+
+    #[test]
+    fn wasm_legacy_matches_0_1_7() {
+        // the bytes 0.1.7's `line_diff` returns for these texts
+        let old = concat!(
+            "fn main() {\n    let a = 1;\n    let b = 2;\n    println!(\"{}\", a);\n}\n",
+            "\nfn other() {}\nlast\n",
+        );
+        let new = concat!(
+            "// header\nfn main() {\n    let a = 10;\n    println!(\"{}\", a);\n}\n",
+            "fn other() {}\nlast\nextra\n",
+        );
+        let expected = vec![
+            0, 0, 0, 1, 0, 0, 0, 1, 1, // add
+            0, 0, 0, 3, 0, 0, 0, 3, 3, // modify
+            0, 0, 0, 4, 0, 0, 0, 4, 2, // delete
+            0, 0, 0, 6, 0, 0, 0, 6, 2, // delete
+            0, 0, 0, 8, 0, 0, 0, 8, 1, // add
+        ];
+        assert!(u8_vec_compare(line_diff_legacy(old, new), expected));
+    }
+
+    #[test]
+    fn wasm_with_options() {
+        let options = DiffOptions {
+            algorithm: DiffAlgorithm::Myers,
+            ..DiffOptions::default()
+        };
+        let out = line_diff_with_options_legacy("c\nb\nb\n", "b\nc\nb\n", &options);
+        let expected = vec![0, 0, 0, 1, 0, 0, 0, 1, 1, 0, 0, 0, 3, 0, 0, 0, 3, 2];
+        assert!(u8_vec_compare(out, expected));
+    }
+
+    #[test]
+    fn wasm_ignore_whitespace() {
+        let mut options = DiffOptions::new();
+        options.ignore_whitespace = true;
+        let out = line_diff_with_options_legacy("a b\n", "a  b\n", &options);
+        let expected = vec![];
+        assert!(u8_vec_compare(out, expected));
     }
 
     #[test]
     fn wasm_single_add() {
-        let out = line_diff("", "hello, world\n");
+        let out = line_diff_legacy("", "hello, world\n");
         let expected = vec![0, 0, 0, 1, 0, 0, 0, 1, 1];
-        assert_eq!(u8_vec_compare(out, expected), true);
+        assert!(u8_vec_compare(out, expected));
+    }
+
+    #[test]
+    fn wasm_versioned_format() {
+        let out = line_diff("hello, world\na\nb\n", "hello, test\n");
+        let expected = vec![
+            b'L', b'D', b'I', b'F', 1, 0, 0, 0, 2, 0, // header
+            0, 0, 0, 1, 0, 0, 0, 1, 3, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1, // modify
+            0, 0, 0, 2, 0, 0, 0, 2, 2, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 2, // delete
+        ];
+        assert!(u8_vec_compare(out, expected));
+
+        let options = DiffOptions {
+            compact_encoding: true,
+            ..DiffOptions::default()
+        };
+        let out = line_diff_with_options("hello, world\na\nb\n", "hello, test\n", &options);
+        let expected = vec![
+            b'L', b'D', b'I', b'F', 1, 0, 0, 0, 2, 1, // header
+            1, 1, 3, 1, 1, 1, // modify
+            2, 2, 2, 2, 3, 2, // delete
+        ];
+        assert!(u8_vec_compare(out.clone(), expected));
+        assert_eq!(
+            decode_line_diff(&out).unwrap(),
+            LineDiffResult::Text {
+                markers: diff("hello, world\na\nb\n", "hello, test\n"),
+                approximate: false
+            }
+        );
+    }
+
+    #[test]
+    fn js_objects_match_bytes() {
+        let result = LineDiffResult::Text {
+            markers: diff("hello, world\na\nb\n", "hello, test\n"),
+            approximate: false,
+        };
+        assert_eq!(
+            serde_json::to_string(&result).unwrap(),
+            "{\"binary\":false,\"markers\":[\
+             {\"startLine\":1,\"endLine\":1,\"kind\":3,\"oldStartLine\":1,\"oldEndLine\":1,\"deletedLineCount\":1},\
+             {\"startLine\":2,\"endLine\":2,\"kind\":2,\"oldStartLine\":2,\"oldEndLine\":3,\"deletedLineCount\":2}\
+             ],\"approximate\":false}"
+        );
     }
 }
@@ -0,0 +1,69 @@
+use std::ops::Range;
+
+// Splits text into lines that keep their `\n`, `\r\n` or `\r` ending, like
+// similar's `tokenize_lines` but scanning bytes rather than decoding chars,
+// which is several times faster on large files. Line endings are ASCII, so
+// they never show up inside a multi-byte char.
+pub(crate) fn split_lines(text: &str) -> Vec<&str> {
+    line_ranges(text.as_bytes())
+        .map(|range| &text[range])
+        .collect()
+}
+
+// `split_lines` for text that may not be valid UTF-8.
+pub(crate) fn split_byte_lines(bytes: &[u8]) -> Vec<&[u8]> {
+    line_ranges(bytes).map(|range| &bytes[range]).collect()
+}
+
+fn line_ranges(bytes: &[u8]) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut start = 0;
+    std::iter::from_fn(move || {
+        if start >= bytes.len() {
+            return None;
+        }
+        let mut end = match bytes[start..]
+            .iter()
+            .position(|&b| b == b'\n' || b == b'\r')
+        {
+            Some(at) => start + at + 1,
+            None => bytes.len(),
+        };
+        if bytes[end - 1] == b'\r' && bytes.get(end) == Some(&b'\n') {
+            end += 1;
+        }
+        let range = start..end;
+        start = end;
+        Some(range)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use similar::DiffableStr;
+
+    use crate::lines::{split_byte_lines, split_lines};
+
+    #[test]
+    fn same_lines_as_similar() {
+        for text in &[
+            "",
+            "a",
+            "a\n",
+            "\n\n",
+            "a\r\nb\rc\nd",
+            "\r",
+            "\r\r\n\n\r",
+            "caf\u{e9}\r\n\u{1F600}\rend",
+        ] {
+            assert_eq!(split_lines(text), text.tokenize_lines(), "{:?}", text);
+            assert_eq!(
+                split_byte_lines(text.as_bytes()),
+                text.as_bytes().tokenize_lines(),
+                "{:?}",
+                text
+            );
+        }
+        let invalid: &[u8] = b"\xff\r\n\xe2\x82\n\xc3\r";
+        assert_eq!(split_byte_lines(invalid), invalid.tokenize_lines());
+    }
+}
@@ -0,0 +1,326 @@
+use std::borrow::Cow;
+
+use similar::DiffOp;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::lines::split_lines;
+use crate::{diff_line_ops, encode_diffs, DiffOptions, Hunk, HunkKind};
+#[cfg(feature = "wasm")]
+use crate::{to_js_diffs, LineDiffArray};
+
+/// How `merge3` writes conflicts into the merged text.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ConflictStyle {
+    /// `<<<<<<<`, `=======` and `>>>>>>>` around ours and theirs.
+    Merge = 0,
+    /// Like `Merge` with the base lines after a `|||||||` marker, like git's
+    /// `merge.conflictStyle = diff3`.
+    Diff3 = 1,
+}
+
+/// The outcome of `merge3`.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, PartialEq)]
+pub struct MergeResult {
+    text: String,
+    conflicts: Vec<Hunk>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl MergeResult {
+    /// The merged text, with conflict markers around every conflict.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn text(&self) -> String {
+        self.text.clone()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn has_conflicts(&self) -> bool {
+        !self.conflicts.is_empty()
+    }
+
+    /// The conflicts in the same byte format as `line_diff`, with the
+    /// conflict kind (4). The line range covers the conflict markers in the
+    /// merged text, the old line range is the base lines both sides changed.
+    pub fn conflicts(&self) -> Vec<u8> {
+        encode_diffs(&self.conflicts, false, false)
+    }
+
+    /// The conflicts as `LineDiff` objects, like `line_diff_objects`.
+    #[cfg(feature = "wasm")]
+    pub fn conflict_objects(&self) -> Result<LineDiffArray, JsValue> {
+        to_js_diffs(&self.conflicts)
+    }
+}
+
+/// Merges the changes from `base` to `ours` and from `base` to `theirs`,
+/// using the same line diff as `line_diff`.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn merge3(base: &str, ours: &str, theirs: &str, style: ConflictStyle) -> MergeResult {
+    let base_lines = split_lines(base);
+    let our_lines = split_lines(ours);
+    let their_lines = split_lines(theirs);
+    let options = DiffOptions::default();
+    let ours_by_base = matched_lines(
+        &diff_line_ops(&base_lines, &our_lines, &options, None, None),
+        &base_lines,
+    );
+    let theirs_by_base = matched_lines(
+        &diff_line_ops(&base_lines, &their_lines, &options, None, None),
+        &base_lines,
+    );
+
+    let mut merged: Vec<Cow<str>> = Vec::new();
+    let mut conflicts = Vec::new();
+    let (mut base_at, mut ours_at, mut theirs_at) = (0, 0, 0);
+
+    while base_at < base_lines.len() || ours_at < our_lines.len() || theirs_at < their_lines.len() {
+        // copy base lines that are unchanged on both sides
+        if base_at < base_lines.len()
+            && ours_by_base[base_at] == Some(ours_at)
+            && theirs_by_base[base_at] == Some(theirs_at)
+        {
+            merged.push(Cow::Borrowed(base_lines[base_at]));
+            base_at += 1;
+            ours_at += 1;
+            theirs_at += 1;
+            continue;
+        }
+
+        // otherwise everything up to the next line unchanged on both sides
+        // was changed by at least one of them
+        let (base_end, ours_end, theirs_end) = (base_at..base_lines.len())
+            .find_map(|i| Some((i, ours_by_base[i]?, theirs_by_base[i]?)))
+            .unwrap_or((base_lines.len(), our_lines.len(), their_lines.len()));
+        let base_chunk = &base_lines[base_at..base_end];
+        let our_chunk = &our_lines[ours_at..ours_end];
+        let their_chunk = &their_lines[theirs_at..theirs_end];
+
+        if our_chunk == base_chunk {
+            merged.extend(their_chunk.iter().map(|line| Cow::Borrowed(*line)));
+        } else if their_chunk == base_chunk || our_chunk == their_chunk {
+            merged.extend(our_chunk.iter().map(|line| Cow::Borrowed(*line)));
+        } else {
+            let start_line = merged.len() as u32 + 1;
+            merged.push(Cow::Borrowed("<<<<<<< ours\n"));
+            push_chunk(&mut merged, our_chunk);
+            if style == ConflictStyle::Diff3 {
+                merged.push(Cow::Borrowed("||||||| base\n"));
+                push_chunk(&mut merged, base_chunk);
+            }
+            merged.push(Cow::Borrowed("=======\n"));
+            push_chunk(&mut merged, their_chunk);
+            merged.push(Cow::Borrowed(">>>>>>> theirs\n"));
+            conflicts.push(Hunk {
+                start_line,
+                end_line: merged.len() as u32,
+                kind: HunkKind::Conflict,
+                old_start_line: base_at as u32 + 1,
+                old_end_line: (base_end as u32).max(base_at as u32 + 1),
+                deleted_line_count: base_chunk.len() as u32,
+            });
+        }
+
+        base_at = base_end;
+        ours_at = ours_end;
+        theirs_at = theirs_end;
+    }
+
+    MergeResult {
+        text: merged.concat(),
+        conflicts,
+    }
+}
+
+// For every base line, the line it is equal to on the other side, if any.
+fn matched_lines(ops: &[DiffOp], base_lines: &[&str]) -> Vec<Option<usize>> {
+    let mut matched = vec![None; base_lines.len()];
+    for op in ops {
+        if let DiffOp::Equal {
+            old_index,
+            new_index,
+            len,
+        } = *op
+        {
+            for offset in 0..len {
+                matched[old_index + offset] = Some(new_index + offset);
+            }
+        }
+    }
+    matched
+}
+
+// Conflict markers go on their own line even when the chunk before them
+// is the end of a file without a final newline.
+fn push_chunk<'a>(merged: &mut Vec<Cow<'a, str>>, chunk: &[&'a str]) {
+    for line in chunk {
+        if line.ends_with(['\r', '\n']) {
+            merged.push(Cow::Borrowed(*line));
+        } else {
+            merged.push(Cow::Owned(format!("{}\n", line)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::merge::{merge3, ConflictStyle};
+    use crate::{Hunk, HunkKind};
+
+    const BASE: &str = "a\nb\nc\nd\ne\n";
+
+    #[test]
+    fn clean_merge() {
+        let ours = "A\nb\nc\nd\ne\n";
+        let theirs = "a\nb\nc\nd\nE\nf\n";
+        let result = merge3(BASE, ours, theirs, ConflictStyle::Merge);
+        assert_eq!(result.text(), "A\nb\nc\nd\nE\nf\n");
+        assert!(!result.has_conflicts());
+    }
+
+    #[test]
+    fn same_change_on_both_sides() {
+        let ours = "a\nB\nc\nd\ne\n";
+        let result = merge3(BASE, ours, ours, ConflictStyle::Merge);
+        assert_eq!(result.text(), ours);
+        assert!(!result.has_conflicts());
+    }
+
+    #[test]
+    fn conflict() {
+        let ours = "a\nours\nc\nd\ne\n";
+        let theirs = "a\ntheirs\nc\nd\nE\n";
+        let result = merge3(BASE, ours, theirs, ConflictStyle::Merge);
+        assert_eq!(
+            result.text(),
+            "a\n<<<<<<< ours\nours\n=======\ntheirs\n>>>>>>> theirs\nc\nd\nE\n"
+        );
+        assert_eq!(
+            result.conflicts,
+            vec![Hunk {
+                start_line: 2,
+                end_line: 6,
+                kind: HunkKind::Conflict,
+                old_start_line: 2,
+                old_end_line: 2,
+                deleted_line_count: 1,
+            }]
+        );
+        assert_eq!(
+            result.conflicts(),
+            vec![
+                b'L', b'D', b'I', b'F', 1, 0, 0, 0, 1, 0, // header
+                0, 0, 0, 2, 0, 0, 0, 6, 4, 0, 0, 0, 2, 0, 0, 0, 2, 0, 0, 0, 1,
+            ]
+        );
+    }
+
+    #[test]
+    fn diff3_conflict_style() {
+        let ours = "a\nb\nc\nd\ne\nours";
+        let theirs = "a\nb\nc\nd\ne\ntheirs\n";
+        let result = merge3(BASE, ours, theirs, ConflictStyle::Diff3);
+        assert_eq!(
+            result.text(),
+            "a\nb\nc\nd\ne\n<<<<<<< ours\nours\n||||||| base\n=======\ntheirs\n>>>>>>> theirs\n"
+        );
+        assert_eq!(
+            result.conflicts,
+            vec![Hunk {
+                start_line: 6,
+                end_line: 11,
+                kind: HunkKind::Conflict,
+                old_start_line: 6,
+                old_end_line: 6,
+                deleted_line_count: 0,
+            }]
+        );
+    }
+
+    fn conflict_at(start_line: u32, end_line: u32, old_start_line: u32, deleted: u32) -> Hunk {
+        Hunk {
+            start_line,
+            end_line,
+            kind: HunkKind::Conflict,
+            old_start_line,
+            old_end_line: old_start_line + deleted.max(1) - 1,
+            deleted_line_count: deleted,
+        }
+    }
+
+    #[test]
+    fn overlapping_edits_that_agree() {
+        let ours = "a\nB\nc\nD\ne\n";
+        let theirs = "a\nB\nc\nd\ne\nf\n";
+        let result = merge3(BASE, ours, theirs, ConflictStyle::Merge);
+        assert_eq!(result.text(), "a\nB\nc\nD\ne\nf\n");
+        assert!(!result.has_conflicts());
+
+        // both delete the same lines, one of them also changes the next one
+        let ours = "a\nd\ne\n";
+        let theirs = "a\nD\ne\n";
+        let result = merge3(BASE, ours, theirs, ConflictStyle::Merge);
+        assert_eq!(
+            result.text(),
+            "a\n<<<<<<< ours\nd\n=======\nD\n>>>>>>> theirs\ne\n"
+        );
+        assert_eq!(result.conflicts, vec![conflict_at(2, 6, 2, 3)]);
+    }
+
+    #[test]
+    fn conflicts_at_the_start_and_end() {
+        let ours = "first\nb\nc\nd\nlast\n";
+        let theirs = "1\nb\nc\nd\n5\n";
+        let result = merge3(BASE, ours, theirs, ConflictStyle::Merge);
+        assert_eq!(
+            result.text(),
+            "<<<<<<< ours\nfirst\n=======\n1\n>>>>>>> theirs\nb\nc\nd\n\
+             <<<<<<< ours\nlast\n=======\n5\n>>>>>>> theirs\n"
+        );
+        assert_eq!(
+            result.conflicts,
+            vec![conflict_at(1, 5, 1, 1), conflict_at(9, 13, 5, 1)]
+        );
+    }
+
+    #[test]
+    fn delete_and_modify_conflict() {
+        let ours = "a\nb\nd\ne\n";
+        let theirs = "a\nb\nC\nd\ne\n";
+        let result = merge3(BASE, ours, theirs, ConflictStyle::Diff3);
+        assert_eq!(
+            result.text(),
+            "a\nb\n<<<<<<< ours\n||||||| base\nc\n=======\nC\n>>>>>>> theirs\nd\ne\n"
+        );
+        assert_eq!(result.conflicts, vec![conflict_at(3, 8, 3, 1)]);
+
+        let result = merge3(BASE, theirs, ours, ConflictStyle::Merge);
+        assert_eq!(
+            result.text(),
+            "a\nb\n<<<<<<< ours\nC\n=======\n>>>>>>> theirs\nd\ne\n"
+        );
+        assert_eq!(result.conflicts, vec![conflict_at(3, 6, 3, 1)]);
+    }
+
+    #[test]
+    fn no_trailing_newline() {
+        let base = "a\nb\nc";
+        let result = merge3(base, "A\nb\nc", "a\nb\nC", ConflictStyle::Merge);
+        assert_eq!(result.text(), "A\nb\nC");
+        assert!(!result.has_conflicts());
+
+        // adding the newline is a change of the last line
+        let result = merge3(base, "a\nb\nc\n", "a\nb\nc", ConflictStyle::Merge);
+        assert_eq!(result.text(), "a\nb\nc\n");
+        assert!(!result.has_conflicts());
+
+        let result = merge3(base, "a\nb\nours", "a\nb\ntheirs", ConflictStyle::Merge);
+        assert_eq!(
+            result.text(),
+            "a\nb\n<<<<<<< ours\nours\n=======\ntheirs\n>>>>>>> theirs\n"
+        );
+        assert_eq!(result.conflicts, vec![conflict_at(3, 7, 3, 1)]);
+    }
+}
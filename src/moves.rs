@@ -0,0 +1,280 @@
+use std::hash::Hash;
+
+use rustc_hash::FxHashMap;
+use similar::ChangeTag;
+
+use crate::{Hunk, HunkKind};
+
+// A moved block has at least this many lines and, like git's
+// `--color-moved`, this many alphanumeric characters, so that closing braces
+// or blank lines that happen to match elsewhere are not reported as moves.
+const MIN_MOVE_LINES: usize = 3;
+const MIN_MOVE_CHARS: usize = 20;
+// Lines that were deleted more often than this, like `}`, do not start a
+// block. They can still be part of one, this only bounds the search.
+const MAX_CANDIDATES: usize = 64;
+
+// A deleted or inserted line of the change stream.
+struct Changed {
+    // 0-based line in its document
+    line: usize,
+    // index into the tags
+    tag: usize,
+    // which run of changes between equal lines it is part of
+    run: usize,
+    // the 1-based new line a delete caret for it sits in front of
+    new_line: u32,
+}
+
+// How much of a line counts towards `MIN_MOVE_CHARS`.
+pub(crate) fn weight(line: &[u8]) -> usize {
+    line.iter().filter(|b| b.is_ascii_alphanumeric()).count()
+}
+
+// Finds blocks of lines that were deleted in one place and inserted with the
+// same keys in another, marks them as ignored in `tags` so they do not
+// become delete carets or adds, and returns a `MovedFrom` caret and a
+// `MovedTo` marker for each. Both carry the block's old lines, which is how
+// they are paired up. Lines inserted and deleted in the same run of changes
+// are left alone, that is not a move.
+pub(crate) fn detect_moves<K: Hash + Eq>(
+    tags: &mut [(ChangeTag, bool)],
+    old_keys: &[K],
+    new_keys: &[K],
+    new_weight: impl Fn(usize) -> usize,
+) -> Vec<Hunk> {
+    let mut deleted = Vec::new();
+    let mut inserted = Vec::new();
+    let (mut old_line, mut new_line, mut run, mut in_run) = (0, 0, 0, false);
+    for (tag, &(change, ignored)) in tags.iter().enumerate() {
+        if change == ChangeTag::Equal {
+            in_run = false;
+            old_line += 1;
+            new_line += 1;
+            continue;
+        }
+        if !in_run {
+            run += 1;
+            in_run = true;
+        }
+        let line = if change == ChangeTag::Delete {
+            old_line
+        } else {
+            new_line
+        };
+        let changed = Changed {
+            line,
+            tag,
+            run,
+            new_line: new_line as u32 + 1,
+        };
+        match (change, ignored) {
+            (ChangeTag::Delete, false) => deleted.push(changed),
+            (ChangeTag::Insert, false) => inserted.push(changed),
+            _ => {}
+        }
+        if change == ChangeTag::Delete {
+            old_line += 1;
+        } else {
+            new_line += 1;
+        }
+    }
+
+    // where each line is in `deleted` and `inserted`
+    let mut deleted_at = vec![None; old_keys.len()];
+    for (i, changed) in deleted.iter().enumerate() {
+        deleted_at[changed.line] = Some(i);
+    }
+    let mut inserted_at = vec![None; new_keys.len()];
+    for (i, changed) in inserted.iter().enumerate() {
+        inserted_at[changed.line] = Some(i);
+    }
+    let mut candidates: FxHashMap<&K, Vec<usize>> = FxHashMap::default();
+    for (i, changed) in deleted.iter().enumerate() {
+        candidates
+            .entry(&old_keys[changed.line])
+            .or_default()
+            .push(i);
+    }
+
+    let mut moved_old = vec![false; deleted.len()];
+    let mut moved_new = vec![false; inserted.len()];
+    // how many lines from `deleted[d]` on match the lines from `inserted[i]` on
+    let block_len = |d: usize, i: usize, moved_old: &[bool], moved_new: &[bool]| {
+        let (from, to) = (&deleted[d], &inserted[i]);
+        (0..)
+            .take_while(|&k| {
+                let d = deleted_at.get(from.line + k).copied().flatten();
+                let i = inserted_at.get(to.line + k).copied().flatten();
+                match (d, i) {
+                    (Some(d), Some(i)) => {
+                        !moved_old[d]
+                            && !moved_new[i]
+                            && deleted[d].run == from.run
+                            && inserted[i].run == to.run
+                            && old_keys[from.line + k] == new_keys[to.line + k]
+                    }
+                    _ => false,
+                }
+            })
+            .count()
+    };
+
+    let mut moves = Vec::new();
+    for i in 0..inserted.len() {
+        if moved_new[i] {
+            continue;
+        }
+        let to = &inserted[i];
+        let starts = match candidates.get(&new_keys[to.line]) {
+            Some(starts) if starts.len() <= MAX_CANDIDATES => starts,
+            _ => continue,
+        };
+        let best = starts
+            .iter()
+            .filter(|&&d| !moved_old[d] && deleted[d].run != to.run)
+            .map(|&d| (d, block_len(d, i, &moved_old, &moved_new)))
+            .fold(None, |best: Option<(usize, usize)>, (d, len)| match best {
+                Some((_, best_len)) if best_len >= len => best,
+                _ => Some((d, len)),
+            });
+        let (d, len) = match best {
+            Some(best) => best,
+            None => continue,
+        };
+        let chars: usize = (to.line..to.line + len).map(&new_weight).sum();
+        if len < MIN_MOVE_LINES || chars < MIN_MOVE_CHARS {
+            continue;
+        }
+
+        let from = &deleted[d];
+        for k in 0..len {
+            moved_old[d + k] = true;
+            moved_new[i + k] = true;
+            tags[deleted[d + k].tag].1 = true;
+            tags[inserted[i + k].tag].1 = true;
+        }
+        let (old_start, len) = (from.line as u32 + 1, len as u32);
+        moves.push(Hunk {
+            start_line: from.new_line,
+            end_line: from.new_line,
+            kind: HunkKind::MovedFrom,
+            old_start_line: old_start,
+            old_end_line: old_start + len - 1,
+            deleted_line_count: len,
+        });
+        moves.push(Hunk {
+            start_line: to.line as u32 + 1,
+            end_line: to.line as u32 + len,
+            kind: HunkKind::MovedTo,
+            old_start_line: old_start,
+            old_end_line: old_start + len - 1,
+            deleted_line_count: 0,
+        });
+    }
+    moves
+}
+
+// Adds the markers of moved blocks in between the others, by new line.
+pub(crate) fn with_moves(mut hunks: Vec<Hunk>, moves: Vec<Hunk>) -> Vec<Hunk> {
+    if !moves.is_empty() {
+        hunks.extend(moves);
+        hunks.sort_by_key(|hunk| hunk.start_line);
+    }
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+    use similar::ChangeTag;
+
+    use crate::moves::{detect_moves, weight};
+    use crate::{diff_with_options, DiffOptions, Hunk, HunkKind};
+
+    fn moves_options() -> DiffOptions {
+        DiffOptions {
+            detect_moves: true,
+            ..DiffOptions::default()
+        }
+    }
+
+    fn hunk(kind: HunkKind, lines: (u32, u32), old_lines: (u32, u32), deleted: u32) -> Hunk {
+        Hunk {
+            start_line: lines.0,
+            end_line: lines.1,
+            kind,
+            old_start_line: old_lines.0,
+            old_end_line: old_lines.1,
+            deleted_line_count: deleted,
+        }
+    }
+
+    const FUNCTION: &str = "fn moved() {\n    let total = compute();\n    total\n}\n";
+
+    #[test]
+    fn function_moved_down() {
+        let middle = "m1\nm2\nm3\nm4\nm5\nm6\n";
+        let old = format!("start\n{}{}end\n", FUNCTION, middle);
+        let new = format!("start\n{}{}end\n", middle, FUNCTION);
+        let moved = vec![
+            hunk(HunkKind::MovedFrom, (2, 2), (2, 5), 4),
+            hunk(HunkKind::MovedTo, (8, 11), (2, 5), 0),
+        ];
+        assert_eq!(diff_with_options(&old, &new, &moves_options()), moved);
+        // without the option it is a delete and an add
+        let plain = diff_with_options(&old, &new, &DiffOptions::default());
+        assert_eq!(
+            plain.iter().map(|hunk| hunk.kind).collect::<Vec<_>>(),
+            vec![HunkKind::Delete, HunkKind::Add]
+        );
+    }
+
+    #[test]
+    fn moved_and_edited() {
+        let old = format!("{}a\nb\nc\nd\ne\nf\n", FUNCTION);
+        let new = format!("a\nB\nc\nd\ne\nf\nnew\n{}", FUNCTION);
+        assert_eq!(
+            diff_with_options(&old, &new, &moves_options()),
+            vec![
+                hunk(HunkKind::MovedFrom, (1, 1), (1, 4), 4),
+                hunk(HunkKind::Modify, (2, 2), (6, 6), 1),
+                hunk(HunkKind::Add, (7, 7), (11, 11), 0),
+                hunk(HunkKind::MovedTo, (8, 11), (1, 4), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn small_blocks_are_not_moves() {
+        let options = moves_options();
+        for (old, new) in &[
+            // too few lines
+            (
+                "x = compute_total(a, b)\ny\nz\n",
+                "y\nz\nx = compute_total(a, b)\n",
+            ),
+            // too few characters
+            ("}\n}\n\n}\na\nb\n", "a\nb\n}\n}\n\n}\n"),
+        ] {
+            let out = diff_with_options(old, new, &options);
+            assert_eq!(out, diff_with_options(old, new, &DiffOptions::default()));
+        }
+    }
+
+    #[test]
+    fn replaced_in_place_is_not_a_move() {
+        // e.g. what is left of a diff that ran into its deadline
+        let keys = [
+            "fn moved() {",
+            "    let total = compute();",
+            "    total",
+            "}",
+        ];
+        let mut tags: Vec<_> = std::iter::repeat_n((ChangeTag::Delete, false), 4)
+            .chain(std::iter::repeat_n((ChangeTag::Insert, false), 4))
+            .collect();
+        let moves = detect_moves(&mut tags, &keys, &keys, |i| weight(keys[i].as_bytes()));
+        assert_eq!(moves, vec![]);
+        assert!(tags.iter().all(|&(_, ignored)| !ignored));
+    }
+}
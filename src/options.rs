@@ -0,0 +1,133 @@
+use std::borrow::Cow;
+
+use similar::Algorithm;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+/// The line diffing algorithm to run.
+///
+/// Patience gives the most readable gutters for hand-written code, Myers is
+/// faster on huge files and LCS tends to do better on generated output.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum DiffAlgorithm {
+    Myers = 0,
+    Patience = 1,
+    Lcs = 2,
+}
+
+impl From<DiffAlgorithm> for Algorithm {
+    fn from(algorithm: DiffAlgorithm) -> Algorithm {
+        match algorithm {
+            DiffAlgorithm::Myers => Algorithm::Myers,
+            DiffAlgorithm::Patience => Algorithm::Patience,
+            DiffAlgorithm::Lcs => Algorithm::Lcs,
+        }
+    }
+}
+
+/// Options for `line_diff_with_options` and `DiffSession.with_options`.
+///
+/// The whitespace options only change which lines compare equal, markers
+/// still point at lines of the real new text.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct DiffOptions {
+    pub algorithm: DiffAlgorithm,
+    /// Treat `\r\n`, `\r` and `\n` line endings as the same.
+    pub ignore_line_endings: bool,
+    /// Ignore whitespace at the end of a line.
+    pub ignore_trailing_whitespace: bool,
+    /// Ignore all whitespace within a line, like `git diff -w`.
+    pub ignore_whitespace: bool,
+    /// Drop changes that only add or remove blank lines.
+    pub ignore_blank_lines: bool,
+    /// Encode results with varints instead of big-endian u32s, which is
+    /// usually less than half the size.
+    pub compact_encoding: bool,
+    /// Give up on finding the best alignment after this many milliseconds
+    /// and fall back to a coarser but still correct one, 0 for no limit.
+    pub timeout_ms: u32,
+    /// Report blocks of lines that were moved elsewhere as `MovedFrom` and
+    /// `MovedTo` markers instead of a delete and an add.
+    pub detect_moves: bool,
+    /// Only pair a deleted and an inserted line into a modify when they are
+    /// at least this similar, from 0 to 1 by the share of characters they
+    /// have in common. Other lines become deletes and adds. 0 pairs them in
+    /// order, however different they are.
+    ///
+    /// Runs of more than 1000 deleted times inserted lines pair each deleted
+    /// line with the most similar of the next 16 inserted lines rather than
+    /// finding the best pairs. Lines compared after `timeout_ms`, or still
+    /// being compared when it runs out, are not paired.
+    pub similarity_threshold: f32,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl DiffOptions {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> DiffOptions {
+        DiffOptions::default()
+    }
+}
+
+impl Default for DiffOptions {
+    fn default() -> DiffOptions {
+        DiffOptions {
+            algorithm: DiffAlgorithm::Patience,
+            ignore_line_endings: false,
+            ignore_trailing_whitespace: false,
+            ignore_whitespace: false,
+            ignore_blank_lines: false,
+            compact_encoding: false,
+            timeout_ms: 0,
+            detect_moves: false,
+            similarity_threshold: 0.0,
+        }
+    }
+}
+
+impl DiffOptions {
+    // What a line is compared by, with the whitespace the options ignore
+    // removed.
+    pub(crate) fn line_key<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        if !self.ignore_line_endings && !self.ignore_trailing_whitespace && !self.ignore_whitespace
+        {
+            return Cow::Borrowed(line);
+        }
+
+        let content = line.trim_end_matches(['\r', '\n']);
+        let mut ending = &line[content.len()..];
+        if self.ignore_line_endings && !ending.is_empty() {
+            ending = "\n";
+        }
+
+        let mut key = if self.ignore_whitespace {
+            content.chars().filter(|c| !c.is_whitespace()).collect()
+        } else if self.ignore_trailing_whitespace {
+            content.trim_end().to_string()
+        } else {
+            content.to_string()
+        };
+        key.push_str(ending);
+        Cow::Owned(key)
+    }
+
+    // `line_key` for byte input. Lines that are not valid UTF-8 are compared
+    // byte for byte.
+    pub(crate) fn byte_line_key<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]> {
+        match std::str::from_utf8(line).map(|line| self.line_key(line)) {
+            Ok(Cow::Borrowed(key)) => Cow::Borrowed(key.as_bytes()),
+            Ok(Cow::Owned(key)) => Cow::Owned(key.into_bytes()),
+            Err(_) => Cow::Borrowed(line),
+        }
+    }
+}
+
+pub(crate) fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+pub(crate) fn is_blank_bytes(line: &[u8]) -> bool {
+    std::str::from_utf8(line).is_ok_and(is_blank)
+}
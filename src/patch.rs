@@ -0,0 +1,417 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::lines::split_lines;
+
+// Like GNU patch, ignore at most this many context lines at either end of a
+// hunk that does not match as is.
+const MAX_FUZZ: usize = 2;
+
+/// The outcome of `apply_patch`: the patched text and one result per hunk.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, PartialEq)]
+pub struct PatchResult {
+    text: String,
+    hunks: Vec<HunkResult>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl PatchResult {
+    /// The text with every hunk that could be placed applied.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn text(&self) -> String {
+        self.text.clone()
+    }
+
+    /// Whether every hunk applied.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn applied(&self) -> bool {
+        self.hunks.iter().all(|hunk| hunk.applied)
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn hunk_count(&self) -> u32 {
+        self.hunks.len() as u32
+    }
+
+    /// The result of the hunk at `index`, in patch order.
+    pub fn hunk(&self, index: u32) -> Option<HunkResult> {
+        self.hunks.get(index as usize).cloned()
+    }
+}
+
+/// How one hunk of a patch applied.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, PartialEq, Clone)]
+pub struct HunkResult {
+    pub applied: bool,
+    /// How many lines away from where its header (adjusted for the hunks
+    /// before it) said the hunk applied.
+    pub offset: i32,
+    /// How many context lines at either end of the hunk had to be ignored.
+    pub fuzz: u32,
+    reason: Option<&'static str>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl HunkResult {
+    /// Why the hunk did not apply, `undefined` if it did.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn reason(&self) -> Option<String> {
+        self.reason.map(|reason| reason.to_string())
+    }
+}
+
+impl HunkResult {
+    fn failed(reason: &'static str) -> HunkResult {
+        HunkResult {
+            applied: false,
+            offset: 0,
+            fuzz: 0,
+            reason: Some(reason),
+        }
+    }
+}
+
+struct Hunk {
+    old_start: usize,
+    old_len: usize,
+    // (' ', '-' or '+', line including its line ending)
+    lines: Vec<(char, String)>,
+}
+
+/// Applies a single-file unified diff to `text`, placing hunks that moved
+/// with an offset search and, failing that, by ignoring up to two context
+/// lines at either end. Hunks that cannot be placed are skipped and
+/// reported with a reason; file headers and anything between hunks is
+/// ignored.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn apply_patch(text: &str, patch: &str) -> PatchResult {
+    let mut lines: Vec<String> = split_lines(text)
+        .into_iter()
+        .map(|line| line.to_string())
+        .collect();
+    let mut hunks = Vec::new();
+
+    // where hunk headers point to in `lines`, as earlier hunks grow or
+    // shrink the text and drift from their headers
+    let mut delta: isize = 0;
+    let mut drift: isize = 0;
+    // hunks never apply on top of an earlier one
+    let mut min_position = 0;
+
+    for parsed in parse_hunks(patch) {
+        let hunk = match parsed {
+            Ok(hunk) => hunk,
+            Err(reason) => {
+                hunks.push(HunkResult::failed(reason));
+                continue;
+            }
+        };
+
+        let header_position = if hunk.old_len == 0 {
+            hunk.old_start
+        } else {
+            hunk.old_start.saturating_sub(1)
+        } as isize
+            + delta;
+        match place_hunk(&lines, &hunk, header_position + drift, min_position) {
+            Some((position, fuzz)) => {
+                let (leading, trailing) = trimmed_context(&hunk, fuzz);
+                let body = &hunk.lines[leading..hunk.lines.len() - trailing];
+                let old_len = body.iter().filter(|(kind, _)| *kind != '+').count();
+                let new_lines: Vec<String> = body
+                    .iter()
+                    .filter(|(kind, _)| *kind != '-')
+                    .map(|(_, line)| line.clone())
+                    .collect();
+                let new_len = new_lines.len();
+                lines.splice(position..position + old_len, new_lines);
+
+                // the trimmed context stays in place, so measure the offset
+                // from where the untrimmed hunk starts
+                let start = position as isize - leading as isize;
+                drift = start - header_position;
+                delta += new_len as isize - old_len as isize;
+                min_position = position + new_len;
+                hunks.push(HunkResult {
+                    applied: true,
+                    offset: drift as i32,
+                    fuzz: fuzz as u32,
+                    reason: None,
+                });
+            }
+            None => hunks.push(HunkResult::failed("context does not match")),
+        }
+    }
+
+    PatchResult {
+        text: lines.concat(),
+        hunks,
+    }
+}
+
+fn parse_hunks(patch: &str) -> Vec<Result<Hunk, &'static str>> {
+    let mut hunks = Vec::new();
+    let mut patch_lines = split_lines(patch).into_iter().peekable();
+
+    while let Some(line) = patch_lines.next() {
+        let header = match line.strip_prefix("@@ ") {
+            Some(header) => header,
+            None => continue,
+        };
+        let (old_start, old_len, new_len) = match parse_header(header) {
+            Some(ranges) => ranges,
+            None => {
+                hunks.push(Err("malformed hunk header"));
+                continue;
+            }
+        };
+
+        let mut hunk = Hunk {
+            old_start,
+            old_len,
+            lines: Vec::new(),
+        };
+        let mut old_seen = 0;
+        let mut new_seen = 0;
+        while old_seen < old_len || new_seen < new_len {
+            let line = match patch_lines.peek() {
+                Some(&line) => line,
+                None => break,
+            };
+            // some tools strip the space off empty context lines
+            let (kind, content) = match line.chars().next() {
+                Some(kind @ (' ' | '-' | '+')) => (kind, &line[1..]),
+                Some('\r' | '\n') => (' ', line),
+                // left for the outer loop, it may be the next hunk's header
+                _ => break,
+            };
+            patch_lines.next();
+            if kind != '+' {
+                old_seen += 1;
+            }
+            if kind != '-' {
+                new_seen += 1;
+            }
+            let mut content = content.to_string();
+            if patch_lines
+                .peek()
+                .is_some_and(|next| next.starts_with('\\'))
+            {
+                patch_lines.next();
+                content.truncate(content.trim_end_matches(['\r', '\n']).len());
+            }
+            hunk.lines.push((kind, content));
+        }
+
+        if old_seen < old_len || new_seen < new_len {
+            hunks.push(Err("hunk is truncated"));
+        } else {
+            hunks.push(Ok(hunk));
+        }
+    }
+    hunks
+}
+
+// Parses `-a,b +c,d @@` into the old start and length and the new length.
+fn parse_header(header: &str) -> Option<(usize, usize, usize)> {
+    let mut parts = header.split(' ');
+    let (old_start, old_len) = parse_range(parts.next()?.strip_prefix('-')?)?;
+    let (_, new_len) = parse_range(parts.next()?.strip_prefix('+')?)?;
+    Some((old_start, old_len, new_len))
+}
+
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    match range.split_once(',') {
+        Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+// How many leading and trailing context lines to ignore at `fuzz`. At least
+// one old line is kept, an empty old side would match anywhere.
+fn trimmed_context(hunk: &Hunk, fuzz: usize) -> (usize, usize) {
+    let leading = hunk
+        .lines
+        .iter()
+        .take_while(|(kind, _)| *kind == ' ')
+        .count();
+    let trailing = hunk
+        .lines
+        .iter()
+        .rev()
+        .take_while(|(kind, _)| *kind == ' ')
+        .count();
+    let old_len = hunk.lines.iter().filter(|(kind, _)| *kind != '+').count();
+    // in a hunk of only context lines both ends count the same lines
+    let trimmable = old_len.saturating_sub(1);
+    let leading = leading.min(fuzz).min(trimmable);
+    let trailing = trailing.min(fuzz).min(trimmable - leading);
+    (leading, trailing)
+}
+
+// Finds where the old side of the hunk matches, trying every position
+// outwards from `expected` before allowing more fuzz.
+fn place_hunk(
+    lines: &[String],
+    hunk: &Hunk,
+    expected: isize,
+    min_position: usize,
+) -> Option<(usize, usize)> {
+    for fuzz in 0..=MAX_FUZZ {
+        let (leading, trailing) = trimmed_context(hunk, fuzz);
+        // no more context to ignore
+        if fuzz > 0 && (leading, trailing) == trimmed_context(hunk, fuzz - 1) {
+            break;
+        }
+        let old_side: Vec<&str> = hunk.lines[leading..hunk.lines.len() - trailing]
+            .iter()
+            .filter(|(kind, _)| *kind != '+')
+            .map(|(_, line)| line.as_str())
+            .collect();
+        if old_side.len() > lines.len() {
+            continue;
+        }
+
+        let expected = expected + leading as isize;
+        let last = (lines.len() - old_side.len()) as isize;
+        let matches = |position: isize| {
+            position >= min_position as isize
+                && position <= last
+                && lines[position as usize..]
+                    .iter()
+                    .zip(old_side.iter())
+                    .all(|(line, old)| line == old)
+        };
+        let max_distance = last.max(expected) - (min_position as isize).min(expected);
+        for distance in 0..=max_distance.max(0) {
+            if matches(expected + distance) {
+                return Some(((expected + distance) as usize, fuzz));
+            }
+            if distance > 0 && matches(expected - distance) {
+                return Some(((expected - distance) as usize, fuzz));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::patch::{apply_patch, HunkResult};
+
+    const OLD: &str = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+
+    fn applied(offset: i32, fuzz: u32) -> HunkResult {
+        HunkResult {
+            applied: true,
+            offset,
+            fuzz,
+            reason: None,
+        }
+    }
+
+    #[test]
+    fn exact() {
+        let patch =
+            "--- a\n+++ b\n@@ -2,3 +2,3 @@\n 2\n-3\n+three\n 4\n@@ -9,2 +9,3 @@\n 9\n 10\n+11\n";
+        let result = apply_patch(OLD, patch);
+        assert_eq!(result.text(), "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\n");
+        assert!(result.applied());
+        assert_eq!(result.hunk(0), Some(applied(0, 0)));
+        assert_eq!(result.hunk(1), Some(applied(0, 0)));
+    }
+
+    #[test]
+    fn offset() {
+        let text = "0\n0\n1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let patch = "@@ -2,3 +2,3 @@\n 2\n-3\n+three\n 4\n@@ -8,2 +8,1 @@\n 8\n-9\n";
+        let result = apply_patch(text, patch);
+        assert_eq!(result.text(), "0\n0\n1\n2\nthree\n4\n5\n6\n7\n8\n10\n");
+        assert_eq!(result.hunk(0), Some(applied(2, 0)));
+        // the second hunk is expected where the first one drifted to
+        assert_eq!(result.hunk(1), Some(applied(2, 0)));
+    }
+
+    #[test]
+    fn fuzz() {
+        let text = "1\nTWO\n3\n4\n5\n";
+        let patch = "@@ -1,5 +1,5 @@\n 1\n 2\n-3\n+three\n 4\n 5\n";
+        let result = apply_patch(text, patch);
+        assert_eq!(result.text(), "1\nTWO\nthree\n4\n5\n");
+        assert_eq!(result.hunk(0), Some(applied(0, 2)));
+    }
+
+    #[test]
+    fn context_only_hunk() {
+        let patch = "@@ -1,3 +1,3 @@\n a\n b\n c\n";
+        let result = apply_patch("x\n", patch);
+        assert_eq!(result.text(), "x\n");
+        assert!(!result.applied());
+        assert!(apply_patch("a\nb\nc\n", patch).applied());
+    }
+
+    #[test]
+    fn fuzz_keeps_an_old_line() {
+        // ignoring both context lines would leave nothing to match
+        let patch = "@@ -1,2 +1,3 @@\n a\n+x\n b\n";
+        let result = apply_patch("1\n2\n3\n", patch);
+        assert_eq!(result.text(), "1\n2\n3\n");
+        assert!(!result.applied());
+
+        let result = apply_patch("1\nb\n", patch);
+        assert_eq!(result.text(), "1\nx\nb\n");
+        assert_eq!(result.hunk(0), Some(applied(0, 1)));
+    }
+
+    #[test]
+    fn failed_hunks_are_skipped() {
+        let patch = "@@ -2,1 +2,1 @@\n-nope\n+x\n@@ -5 +5 @@\n-5\n+five\n@@ garbage @@\n";
+        let result = apply_patch(OLD, patch);
+        assert_eq!(result.text(), "1\n2\n3\n4\nfive\n6\n7\n8\n9\n10\n");
+        assert!(!result.applied());
+        assert_eq!(result.hunk_count(), 3);
+        assert_eq!(
+            result.hunk(0).unwrap().reason(),
+            Some("context does not match".to_string())
+        );
+        assert_eq!(result.hunk(1), Some(applied(0, 0)));
+        assert_eq!(
+            result.hunk(2).unwrap().reason(),
+            Some("malformed hunk header".to_string())
+        );
+    }
+
+    #[test]
+    fn truncated_hunk() {
+        let result = apply_patch(OLD, "@@ -1,3 +1,3 @@\n 1\n-2\n");
+        assert_eq!(result.text(), OLD);
+        assert_eq!(
+            result.hunk(0).unwrap().reason(),
+            Some("hunk is truncated".to_string())
+        );
+    }
+
+    #[test]
+    fn truncated_hunk_before_a_good_one() {
+        let patch = "@@ -1,3 +1,3 @@\n 1\n-2\n+two\n@@ -8,1 +8,1 @@\n-8\n+eight\n";
+        let result = apply_patch(OLD, patch);
+        assert_eq!(result.hunk_count(), 2);
+        assert_eq!(
+            result.hunk(0).unwrap().reason(),
+            Some("hunk is truncated".to_string())
+        );
+        assert_eq!(result.hunk(1), Some(applied(0, 0)));
+        assert_eq!(result.text(), "1\n2\n3\n4\n5\n6\n7\neight\n9\n10\n");
+    }
+
+    #[test]
+    fn missing_newline_and_empty_file() {
+        let patch = "@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+b\n";
+        assert_eq!(apply_patch("a\nb", patch).text(), "a\nb\n");
+
+        let patch = "@@ -0,0 +1,2 @@\n+a\n+b\n";
+        assert_eq!(apply_patch("", patch).text(), "a\nb\n");
+    }
+}
@@ -0,0 +1,170 @@
+use std::ops::Range;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::lines::split_lines;
+use crate::{diff_with_options, DiffOptions, Hunk, HunkKind};
+
+/// Returns `new_text` with the marker covering `line` (1-based, in the new
+/// document) reverted to its old content, or `undefined` when there is no
+/// marker on that line. When a delete caret sits on the same line as an add
+/// or modify, the add or modify is reverted.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn revert_hunk(
+    old_text: &str,
+    new_text: &str,
+    line: u32,
+    options: &DiffOptions,
+) -> Option<String> {
+    let (old_span, new_span) = find_hunk(old_text, new_text, line, options)?;
+    Some(splice_lines(new_text, new_span, old_text, old_span))
+}
+
+/// Returns `old_text` with only the marker covering `line` (1-based, in the
+/// new document) applied, or `undefined` when there is no marker on that
+/// line. Picks the same marker as `revert_hunk`.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn stage_hunk(
+    old_text: &str,
+    new_text: &str,
+    line: u32,
+    options: &DiffOptions,
+) -> Option<String> {
+    let (old_span, new_span) = find_hunk(old_text, new_text, line, options)?;
+    Some(splice_lines(old_text, old_span, new_text, new_span))
+}
+
+fn find_hunk(
+    old_text: &str,
+    new_text: &str,
+    line: u32,
+    options: &DiffOptions,
+) -> Option<(Range<usize>, Range<usize>)> {
+    // the two halves of a move are reverted like the delete and add they are
+    let options = DiffOptions {
+        detect_moves: false,
+        ..*options
+    };
+    // a delete caret only shares its line with a marker that covers the line
+    // itself, which is the one meant
+    diff_with_options(old_text, new_text, &options)
+        .iter()
+        .filter(|d| d.start_line <= line && line <= d.end_line)
+        .min_by_key(|d| d.kind == HunkKind::Delete)
+        .map(spans)
+}
+
+// The 0-based old and new line ranges a marker swaps between. A delete caret
+// sits in front of `end_line`, so only the equal lines between merged carets
+// are part of its new range.
+fn spans(d: &Hunk) -> (Range<usize>, Range<usize>) {
+    let new_start = d.start_line as usize - 1;
+    let old_start = d.old_start_line as usize - 1;
+    match d.kind {
+        HunkKind::Add => (old_start..old_start, new_start..d.end_line as usize),
+        HunkKind::Delete => (
+            old_start..d.old_end_line as usize,
+            new_start..d.end_line as usize - 1,
+        ),
+        HunkKind::Modify | HunkKind::Conflict => (
+            old_start..d.old_end_line as usize,
+            new_start..d.end_line as usize,
+        ),
+        HunkKind::MovedFrom | HunkKind::MovedTo => unreachable!("moves are not detected"),
+    }
+}
+
+// Replaces the lines `span` of `text` with the lines `from_span` of
+// `from_text`.
+fn splice_lines(
+    text: &str,
+    span: Range<usize>,
+    from_text: &str,
+    from_span: Range<usize>,
+) -> String {
+    let lines = split_lines(text);
+    let from_lines = split_lines(from_text);
+    let mut result = String::with_capacity(text.len());
+    result.extend(lines[..span.start].iter().copied());
+    result.extend(from_lines[from_span].iter().copied());
+    result.extend(lines[span.end..].iter().copied());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::revert::{revert_hunk, stage_hunk};
+    use crate::DiffOptions;
+
+    const BEFORE: &str = "a\nb\nc\nd\ne\n";
+    const AFTER: &str = "a\nB\nc\nnew\nd\n";
+
+    #[test]
+    fn revert_each_hunk() {
+        let options = DiffOptions::default();
+        assert_eq!(
+            revert_hunk(BEFORE, AFTER, 2, &options).unwrap(),
+            "a\nb\nc\nnew\nd\n"
+        );
+        assert_eq!(
+            revert_hunk(BEFORE, AFTER, 4, &options).unwrap(),
+            "a\nB\nc\nd\n"
+        );
+        assert_eq!(
+            revert_hunk(BEFORE, AFTER, 6, &options).unwrap(),
+            "a\nB\nc\nnew\nd\ne\n"
+        );
+        assert_eq!(revert_hunk(BEFORE, AFTER, 3, &options), None);
+    }
+
+    #[test]
+    fn stage_each_hunk() {
+        let options = DiffOptions::default();
+        assert_eq!(
+            stage_hunk(BEFORE, AFTER, 2, &options).unwrap(),
+            "a\nB\nc\nd\ne\n"
+        );
+        assert_eq!(
+            stage_hunk(BEFORE, AFTER, 4, &options).unwrap(),
+            "a\nb\nc\nnew\nd\ne\n"
+        );
+        assert_eq!(
+            stage_hunk(BEFORE, AFTER, 6, &options).unwrap(),
+            "a\nb\nc\nd\n"
+        );
+    }
+
+    #[test]
+    fn revert_merged_delete_carets() {
+        let before = "a\nx\nb\ny\nc\n";
+        let after = "a\nb\nc\n";
+        let options = DiffOptions::default();
+        assert_eq!(revert_hunk(before, after, 3, &options).unwrap(), before);
+        assert_eq!(stage_hunk(before, after, 2, &options).unwrap(), after);
+    }
+
+    #[test]
+    fn caret_on_the_line_of_an_add() {
+        let before = "a\nunrelated\nb\n";
+        let after = "a\nlet z = 0;\nb\n";
+        // a delete caret and an add, both on line 2
+        let options = DiffOptions {
+            similarity_threshold: 0.5,
+            ..DiffOptions::default()
+        };
+        assert_eq!(revert_hunk(before, after, 2, &options).unwrap(), "a\nb\n");
+        assert_eq!(
+            stage_hunk(before, after, 2, &options).unwrap(),
+            "a\nunrelated\nlet z = 0;\nb\n"
+        );
+    }
+
+    #[test]
+    fn revert_multi_line_modify() {
+        let before = "a\nb\nc\n";
+        let after = "A\nB\nc\n";
+        let options = DiffOptions::default();
+        assert_eq!(revert_hunk(before, after, 2, &options).unwrap(), before);
+    }
+}
@@ -0,0 +1,724 @@
+use std::borrow::Cow;
+
+use similar::{capture_diff_slices_deadline, DiffOp};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::clock::{deadline_after, exceeded};
+use crate::format::write_diffs;
+use crate::intern::LineIds;
+use crate::lines::split_lines;
+use crate::{collect_markers, encode_diffs, is_blank, line_tags, DiffBuffer, DiffOptions, Hunk};
+
+// How many unchanged lines on either side of an edit are re-diffed with it.
+const CONTEXT: usize = 3;
+
+/// Keeps a baseline and the current editor document in wasm memory so that
+/// per-keystroke updates only re-diff the region around each edit.
+///
+/// Lines are numbered by their `DiffOptions` key like in a full diff. The
+/// current alignment is kept as similar's `DiffOp`s and an edit only re-diffs
+/// the ops it touches and a few unchanged lines around them, splicing the
+/// result back in.
+///
+/// The markers always describe the current text: every line outside of them
+/// is unchanged from the baseline. They are not always the markers a full
+/// `line_diff` of the same texts gives, though. Alignments elsewhere are
+/// never revisited, and within the re-diffed region the algorithm only sees
+/// that region, so text with many repeated lines, like blank lines or `}`,
+/// often ends up aligned differently.
+///
+/// With `timeout_ms` every re-diff gets its own deadline. Once one runs into
+/// it the markers stay approximate until the next `set_baseline` or
+/// `set_text` finishes in time.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct DiffSession {
+    options: DiffOptions,
+    approximate: bool,
+    line_ids: LineIds,
+    baseline_ids: Vec<u32>,
+    baseline_blank: Vec<bool>,
+    // only kept to compare lines by similarity
+    baseline_lines: Vec<String>,
+    lines: Vec<String>,
+    ids: Vec<u32>,
+    ops: Vec<DiffOp>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl DiffSession {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(baseline: &str, text: &str) -> DiffSession {
+        DiffSession::with_options(baseline, text, &DiffOptions::default())
+    }
+
+    pub fn with_options(baseline: &str, text: &str, options: &DiffOptions) -> DiffSession {
+        let baseline_lines = split_lines(baseline);
+        let lines = owned_lines(text);
+        let mut line_ids = LineIds::new();
+        let baseline_ids = line_ids.intern(line_keys(&baseline_lines, options));
+        let ids = line_ids.intern(line_keys(&lines, options));
+        let mut session = DiffSession {
+            options: *options,
+            approximate: false,
+            line_ids,
+            baseline_ids,
+            baseline_blank: baseline_lines.iter().map(|line| is_blank(line)).collect(),
+            baseline_lines: kept_lines(&baseline_lines, options),
+            lines,
+            ids,
+            ops: Vec::new(),
+        };
+        session.rediff_all();
+        session
+    }
+
+    /// Replaces the baseline (e.g. after a commit) and re-diffs everything.
+    pub fn set_baseline(&mut self, baseline: &str) -> Vec<u8> {
+        let baseline_lines = split_lines(baseline);
+        self.baseline_ids = self
+            .line_ids
+            .intern(line_keys(&baseline_lines, &self.options));
+        self.baseline_blank = baseline_lines.iter().map(|line| is_blank(line)).collect();
+        self.baseline_lines = kept_lines(&baseline_lines, &self.options);
+        self.rediff_all();
+        self.line_diff()
+    }
+
+    /// Replaces the whole current document and re-diffs everything.
+    pub fn set_text(&mut self, text: &str) -> Vec<u8> {
+        self.lines = owned_lines(text);
+        self.ids = self.line_ids.intern(line_keys(&self.lines, &self.options));
+        self.rediff_all();
+        self.line_diff()
+    }
+
+    /// Applies an editor change event to the current document and returns
+    /// the updated markers in the same byte format as `line_diff`.
+    ///
+    /// Lines are 1-based like the markers, columns are 0-based UTF-16 offsets
+    /// into the line as JS sees it. The end position is exclusive.
+    pub fn apply_change(
+        &mut self,
+        start_line: u32,
+        start_column: u32,
+        end_line: u32,
+        end_column: u32,
+        text: &str,
+    ) -> Vec<u8> {
+        self.edit(start_line, start_column, end_line, end_column, text);
+        self.line_diff()
+    }
+
+    /// Like `apply_change`, but writes the markers into `buffer` instead of
+    /// returning a copy. Returns the length of the result.
+    pub fn apply_change_into(
+        &mut self,
+        start_line: u32,
+        start_column: u32,
+        end_line: u32,
+        end_column: u32,
+        text: &str,
+        buffer: &mut DiffBuffer,
+    ) -> u32 {
+        self.edit(start_line, start_column, end_line, end_column, text);
+        self.line_diff_into(buffer)
+    }
+
+    /// The current markers in the same byte format as `line_diff`.
+    pub fn line_diff(&self) -> Vec<u8> {
+        encode_diffs(
+            &self.diffs(),
+            self.options.compact_encoding,
+            self.approximate,
+        )
+    }
+
+    /// Like `line_diff`, written into `buffer`. Returns the length of the
+    /// result.
+    pub fn line_diff_into(&self, buffer: &mut DiffBuffer) -> u32 {
+        let compact = self.options.compact_encoding;
+        write_diffs(buffer.bytes_mut(), &self.diffs(), compact, self.approximate);
+        buffer.length()
+    }
+
+    /// The current document as a string.
+    pub fn text(&self) -> String {
+        self.lines.concat()
+    }
+}
+
+impl DiffSession {
+    fn diffs(&self) -> Vec<Hunk> {
+        let tags = line_tags(
+            &self.ops,
+            &self.options,
+            |i| self.baseline_blank[i],
+            |i| is_blank(&self.lines[i]),
+        );
+        // pairing lines by similarity gets a deadline of its own
+        collect_markers(
+            tags,
+            &self.options,
+            deadline_after(self.options.timeout_ms),
+            &self.baseline_ids,
+            &self.ids,
+            |i| self.baseline_lines[i].as_bytes(),
+            |i| self.lines[i].as_bytes(),
+        )
+    }
+
+    fn rediff_all(&mut self) {
+        self.line_ids
+            .forget_unused([&mut self.baseline_ids, &mut self.ids]);
+        let deadline = deadline_after(self.options.timeout_ms);
+        let ops = capture_diff_slices_deadline(
+            self.options.algorithm.into(),
+            &self.baseline_ids,
+            &self.ids,
+            deadline,
+        );
+        self.ops = renumber(ops, 0, 0);
+        self.approximate = exceeded(deadline);
+    }
+
+    fn edit(
+        &mut self,
+        start_line: u32,
+        start_column: u32,
+        end_line: u32,
+        end_column: u32,
+        text: &str,
+    ) {
+        let line_count = self.lines.len();
+        // a position on the line after the last newline is valid but has
+        // no entry in `lines`
+        let start = (start_line.max(1) as usize - 1).min(line_count);
+        let end = (end_line.max(1) as usize - 1).min(line_count).max(start);
+
+        let first = self.lines.get(start).map_or("", |line| line.as_str());
+        let last = self.lines.get(end).map_or("", |line| line.as_str());
+        let mut replacement = String::new();
+        replacement.push_str(&first[..utf16_to_byte_offset(first, start_column)]);
+        replacement.push_str(text);
+        replacement.push_str(&last[utf16_to_byte_offset(last, end_column)..]);
+
+        // the edited lines run up to and including `end`, unless that is the
+        // phantom line after the final newline
+        let removed_end = (end + 1).min(line_count);
+        let new_lines = owned_lines(&replacement);
+        let new_ids = self.line_ids.intern(line_keys(&new_lines, &self.options));
+        self.splice(start, removed_end, new_lines, new_ids);
+        self.line_ids
+            .forget_unused([&mut self.baseline_ids, &mut self.ids]);
+    }
+
+    // Replaces current lines `start..end` and re-diffs only the ops touching
+    // that range.
+    fn splice(&mut self, start: usize, end: usize, new_lines: Vec<String>, new_ids: Vec<u32>) {
+        // widen to cover any changed op that touches the edit so adjacent
+        // deletes and inserts get re-paired
+        let mut lo = start;
+        let mut hi = end;
+        for op in self.ops.iter() {
+            let range = op.new_range();
+            if !matches!(op, DiffOp::Equal { .. }) && range.start <= hi && range.end >= lo {
+                lo = lo.min(range.start);
+                hi = hi.max(range.end);
+            }
+        }
+        // and by a few unchanged lines on either side, so the changes can
+        // slide into them like they would in a full diff
+        for op in self.ops.iter() {
+            let range = op.new_range();
+            if matches!(op, DiffOp::Equal { .. }) && range.start <= hi && range.end >= lo {
+                lo = lo.min(range.start.max(lo.saturating_sub(CONTEXT)));
+                hi = hi.max(range.end.min(hi + CONTEXT));
+            }
+        }
+
+        split_equal_at(&mut self.ops, lo);
+        split_equal_at(&mut self.ops, hi);
+
+        let first = self
+            .ops
+            .iter()
+            .position(|op| op.new_range().start >= lo)
+            .unwrap_or(self.ops.len());
+        let past = first
+            + self.ops[first..]
+                .iter()
+                .position(|op| op.new_range().end > hi)
+                .unwrap_or(self.ops.len() - first);
+        let old_len = self.baseline_ids.len();
+        let old_lo = self
+            .ops
+            .get(first)
+            .map_or(old_len, |op| op.old_range().start);
+        let old_hi = self
+            .ops
+            .get(past)
+            .map_or(old_len, |op| op.old_range().start);
+
+        let added = new_lines.len();
+        let removed = end - start;
+        self.lines.splice(start..end, new_lines);
+        self.ids.splice(start..end, new_ids);
+        let new_hi = hi + added - removed;
+
+        let deadline = deadline_after(self.options.timeout_ms);
+        let window_ops = capture_diff_slices_deadline(
+            self.options.algorithm.into(),
+            &self.baseline_ids[old_lo..old_hi],
+            &self.ids[lo..new_hi],
+            deadline,
+        );
+        let window_ops = renumber(window_ops, old_lo, lo);
+        self.approximate |= exceeded(deadline);
+
+        for op in self.ops[past..].iter_mut() {
+            *op = shift_op(*op, 0, added as isize - removed as isize);
+        }
+        self.ops.splice(first..past, window_ops);
+    }
+}
+
+// The current document is edited in place, so it keeps its own lines.
+fn owned_lines(text: &str) -> Vec<String> {
+    split_lines(text).into_iter().map(str::to_string).collect()
+}
+
+// The baseline lines, when the options need them after numbering.
+fn kept_lines(lines: &[&str], options: &DiffOptions) -> Vec<String> {
+    if options.similarity_threshold > 0.0 {
+        lines.iter().map(|line| line.to_string()).collect()
+    } else {
+        Vec::new()
+    }
+}
+
+fn line_keys<'a, S: AsRef<str>>(
+    lines: &'a [S],
+    options: &'a DiffOptions,
+) -> impl Iterator<Item = Cow<'a, str>> {
+    lines
+        .iter()
+        .map(move |line| options.line_key(line.as_ref()))
+}
+
+// Clamps to the end of the line's content, before any line terminator.
+fn utf16_to_byte_offset(line: &str, column: u32) -> usize {
+    let content = line.trim_end_matches(['\r', '\n']);
+    let mut units = 0;
+    for (index, c) in content.char_indices() {
+        if units >= column as usize {
+            return index;
+        }
+        units += c.len_utf16();
+    }
+    content.len()
+}
+
+// Splits the equal op that spans new-document line `at`, if any, so that
+// `at` becomes an op boundary.
+fn split_equal_at(ops: &mut Vec<DiffOp>, at: usize) {
+    let index = ops.iter().position(|op| match *op {
+        DiffOp::Equal { new_index, len, .. } => new_index < at && at < new_index + len,
+        _ => false,
+    });
+    if let Some(index) = index {
+        if let DiffOp::Equal {
+            old_index,
+            new_index,
+            len,
+        } = ops[index]
+        {
+            let head = at - new_index;
+            ops[index] = DiffOp::Equal {
+                old_index,
+                new_index,
+                len: head,
+            };
+            ops.insert(
+                index + 1,
+                DiffOp::Equal {
+                    old_index: old_index + head,
+                    new_index: at,
+                    len: len - head,
+                },
+            );
+        }
+    }
+}
+
+// Numbers the ops from their lengths, starting at `old_index` and
+// `new_index`. similar can give a `Delete` the wrong `new_index` after
+// sliding it past equal lines, which only matters to us since we look ops up
+// by their position.
+fn renumber(ops: Vec<DiffOp>, mut old_index: usize, mut new_index: usize) -> Vec<DiffOp> {
+    ops.into_iter()
+        .map(|op| {
+            let (old_len, new_len) = (op.old_range().len(), op.new_range().len());
+            let op = match op {
+                DiffOp::Equal { len, .. } => DiffOp::Equal {
+                    old_index,
+                    new_index,
+                    len,
+                },
+                DiffOp::Delete { old_len, .. } => DiffOp::Delete {
+                    old_index,
+                    old_len,
+                    new_index,
+                },
+                DiffOp::Insert { new_len, .. } => DiffOp::Insert {
+                    old_index,
+                    new_index,
+                    new_len,
+                },
+                DiffOp::Replace {
+                    old_len, new_len, ..
+                } => DiffOp::Replace {
+                    old_index,
+                    old_len,
+                    new_index,
+                    new_len,
+                },
+            };
+            old_index += old_len;
+            new_index += new_len;
+            op
+        })
+        .collect()
+}
+
+fn shift_op(op: DiffOp, old_by: isize, new_by: isize) -> DiffOp {
+    let shift = |index: usize, by: isize| (index as isize + by) as usize;
+    match op {
+        DiffOp::Equal {
+            old_index,
+            new_index,
+            len,
+        } => DiffOp::Equal {
+            old_index: shift(old_index, old_by),
+            new_index: shift(new_index, new_by),
+            len,
+        },
+        DiffOp::Delete {
+            old_index,
+            old_len,
+            new_index,
+        } => DiffOp::Delete {
+            old_index: shift(old_index, old_by),
+            old_len,
+            new_index: shift(new_index, new_by),
+        },
+        DiffOp::Insert {
+            old_index,
+            new_index,
+            new_len,
+        } => DiffOp::Insert {
+            old_index: shift(old_index, old_by),
+            new_index: shift(new_index, new_by),
+            new_len,
+        },
+        DiffOp::Replace {
+            old_index,
+            old_len,
+            new_index,
+            new_len,
+        } => DiffOp::Replace {
+            old_index: shift(old_index, old_by),
+            old_len,
+            new_index: shift(new_index, new_by),
+            new_len,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diff;
+    use crate::diff_with_options;
+    use crate::lines::split_lines;
+    use crate::session::DiffSession;
+    use crate::DiffAlgorithm;
+    use crate::DiffBuffer;
+    use crate::DiffOptions;
+    use crate::HunkKind;
+
+    fn assert_matches_full_diff(session: &DiffSession, baseline: &str) {
+        assert_eq!(session.diffs(), diff(baseline, &session.text()));
+    }
+
+    // Walks the markers in order and checks that the lines between them are
+    // the same on both sides.
+    fn assert_valid_markers(session: &DiffSession, baseline: &str) {
+        let text = session.text();
+        let old_lines = split_lines(baseline);
+        let new_lines = split_lines(&text);
+        let context = format!("{:?} -> {:?}", baseline, text);
+        let (mut old, mut new) = (0, 0);
+        for hunk in session.diffs() {
+            let (new_start, old_start) = (hunk.start_line - 1, hunk.old_start_line - 1);
+            let (new_start, old_start) = (new_start as usize, old_start as usize);
+            assert_eq!(new_start - new, old_start - old, "{}", context);
+            assert_eq!(
+                old_lines[old..old_start],
+                new_lines[new..new_start],
+                "{}",
+                context
+            );
+            match hunk.kind {
+                HunkKind::Add => old = old_start,
+                HunkKind::Modify => old = hunk.old_end_line as usize,
+                // carets on consecutive lines are merged, so the old lines
+                // also hold the lines kept between them
+                HunkKind::Delete => {
+                    let old_range = &old_lines[old_start..hunk.old_end_line as usize];
+                    let kept = &new_lines[new_start..hunk.end_line as usize - 1];
+                    assert_eq!(
+                        old_range.len(),
+                        kept.len() + hunk.deleted_line_count as usize,
+                        "{}",
+                        context
+                    );
+                    let mut rest = old_range.iter();
+                    for line in kept {
+                        assert!(rest.any(|old| old == line), "{}", context);
+                    }
+                    old = hunk.old_end_line as usize;
+                }
+                kind => panic!("unexpected {:?}", kind),
+            }
+            new = match hunk.kind {
+                HunkKind::Delete => hunk.end_line as usize - 1,
+                _ => hunk.end_line as usize,
+            };
+        }
+        assert_eq!(old_lines[old..], new_lines[new..], "{}", context);
+    }
+
+    // A small xorshift so the edits are the same on every run.
+    struct Edits(u64);
+
+    impl Edits {
+        fn next(&mut self, bound: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % bound
+        }
+
+        fn lines(&mut self, count: u64, alphabet: u64) -> String {
+            (0..count)
+                .map(|_| format!("{}\n", self.next(alphabet)))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn initial_state() {
+        let baseline = "a\nb\nc\n";
+        let session = DiffSession::new(baseline, "a\nB\nc\nd\n");
+        assert_matches_full_diff(&session, baseline);
+    }
+
+    #[test]
+    fn typing_into_a_line() {
+        let baseline = "a\nb\nc\n";
+        let mut session = DiffSession::new(baseline, baseline);
+        session.apply_change(2, 1, 2, 1, "x");
+        assert_eq!(session.text(), "a\nbx\nc\n");
+        assert_matches_full_diff(&session, baseline);
+
+        // and back again
+        session.apply_change(2, 1, 2, 2, "");
+        assert_eq!(session.text(), baseline);
+        assert!(session.diffs().is_empty());
+    }
+
+    #[test]
+    fn inserting_and_removing_lines() {
+        let baseline = "a\nb\nc\nd\n";
+        let mut session = DiffSession::new(baseline, baseline);
+        session.apply_change(2, 1, 2, 1, "\nnew\nlines");
+        assert_eq!(session.text(), "a\nb\nnew\nlines\nc\nd\n");
+        assert_matches_full_diff(&session, baseline);
+
+        session.apply_change(1, 0, 3, 0, "");
+        assert_eq!(session.text(), "new\nlines\nc\nd\n");
+        assert_matches_full_diff(&session, baseline);
+    }
+
+    #[test]
+    fn apply_change_into_buffer() {
+        let baseline = "a\nb\nc\n";
+        let mut session = DiffSession::new(baseline, baseline);
+        let mut buffer = DiffBuffer::new();
+        let length = session.apply_change_into(2, 1, 2, 1, "x", &mut buffer);
+        assert_eq!(length, buffer.length());
+        assert_eq!(buffer.bytes_mut().clone(), session.line_diff());
+    }
+
+    #[test]
+    fn typing_at_end_of_document() {
+        let baseline = "a\n";
+        let mut session = DiffSession::new(baseline, baseline);
+        session.apply_change(2, 0, 2, 0, "b");
+        session.apply_change(2, 1, 2, 1, "\n");
+        assert_eq!(session.text(), "a\nb\n");
+        assert_matches_full_diff(&session, baseline);
+    }
+
+    #[test]
+    fn utf16_columns() {
+        let baseline = "h\u{1F600}llo\n";
+        let mut session = DiffSession::new(baseline, baseline);
+        // the emoji is two UTF-16 code units
+        session.apply_change(1, 3, 1, 4, "e");
+        assert_eq!(session.text(), "h\u{1F600}elo\n");
+        assert_matches_full_diff(&session, baseline);
+    }
+
+    #[test]
+    fn edits_in_separate_regions() {
+        let baseline = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+        let mut session = DiffSession::new(baseline, baseline);
+        session.apply_change(2, 0, 2, 1, "two");
+        session.apply_change(8, 0, 9, 0, "");
+        session.apply_change(5, 1, 5, 1, "\nfive and a half");
+        session.apply_change(2, 0, 2, 3, "2");
+        assert_eq!(session.text(), "1\n2\n3\n4\n5\nfive and a half\n6\n7\n9\n");
+        assert_matches_full_diff(&session, baseline);
+    }
+
+    #[test]
+    fn session_with_options() {
+        let baseline = "c\nb\nb\n";
+        let options = DiffOptions {
+            algorithm: DiffAlgorithm::Myers,
+            ..DiffOptions::default()
+        };
+        let mut session = DiffSession::with_options(baseline, "b\nc\nb\n", &options);
+        assert_eq!(
+            session.diffs(),
+            diff_with_options(baseline, &session.text(), &options)
+        );
+
+        session.apply_change(1, 0, 4, 0, "a\nb\nc\n");
+        assert_eq!(
+            session.diffs(),
+            diff_with_options(baseline, &session.text(), &options)
+        );
+    }
+
+    #[test]
+    fn session_ignoring_whitespace() {
+        let baseline = "a\n\nb\n";
+        let options = DiffOptions {
+            ignore_trailing_whitespace: true,
+            ignore_blank_lines: true,
+            ..DiffOptions::default()
+        };
+        let mut session = DiffSession::with_options(baseline, baseline, &options);
+        session.apply_change(1, 1, 1, 1, "  ");
+        session.apply_change(2, 0, 3, 0, "");
+        assert_eq!(session.text(), "a  \nb\n");
+        assert!(session.diffs().is_empty());
+
+        session.apply_change(2, 0, 2, 0, "c");
+        assert_eq!(
+            session.diffs(),
+            diff_with_options(baseline, &session.text(), &options)
+        );
+    }
+
+    #[test]
+    fn session_detecting_moves() {
+        let function = "fn moved() {\n    let total = compute();\n    total\n}\n";
+        let middle = "m1\nm2\nm3\nm4\nm5\nm6\n";
+        let baseline = format!("{}{}", function, middle);
+        let options = DiffOptions {
+            detect_moves: true,
+            ..DiffOptions::default()
+        };
+        let mut session = DiffSession::with_options(&baseline, middle, &options);
+        session.apply_change(7, 0, 7, 0, function);
+        assert_eq!(
+            session.diffs(),
+            diff_with_options(&baseline, &session.text(), &options)
+        );
+        assert!(session
+            .diffs()
+            .iter()
+            .any(|hunk| hunk.kind == HunkKind::MovedTo));
+    }
+
+    #[test]
+    fn session_pairing_by_similarity() {
+        let baseline = "a\nlet a = 1;\nz\n";
+        let options = DiffOptions {
+            similarity_threshold: 0.5,
+            ..DiffOptions::default()
+        };
+        let mut session = DiffSession::with_options(baseline, baseline, &options);
+        session.apply_change(2, 0, 2, 0, "let z = 0;\n");
+        session.apply_change(3, 8, 3, 9, "2");
+        assert_eq!(session.text(), "a\nlet z = 0;\nlet a = 2;\nz\n");
+        assert_eq!(
+            session.diffs(),
+            diff_with_options(baseline, &session.text(), &options)
+        );
+        assert_eq!(session.diffs()[0].kind, HunkKind::Add);
+
+        session.set_baseline("a\nlet b = 1;\nz\n");
+        assert_eq!(
+            session.diffs(),
+            diff_with_options("a\nlet b = 1;\nz\n", &session.text(), &options)
+        );
+    }
+
+    #[test]
+    fn random_edits_keep_valid_markers() {
+        let mut edits = Edits(0x2545_f491_4f6c_dd1d);
+        for algorithm in [DiffAlgorithm::Patience, DiffAlgorithm::Myers] {
+            let options = DiffOptions {
+                algorithm,
+                ..DiffOptions::default()
+            };
+            for _ in 0..300 {
+                // few distinct lines, so many alignments are possible
+                let alphabet = 2 + edits.next(8);
+                let count = edits.next(20);
+                let baseline = edits.lines(count, alphabet);
+                let mut session = DiffSession::with_options(&baseline, &baseline, &options);
+                for _ in 0..8 {
+                    let start_line = edits.next(session.lines.len() as u64 + 1) as u32 + 1;
+                    let end_line = start_line + edits.next(3) as u32;
+                    let count = edits.next(3);
+                    let text = edits.lines(count, alphabet + 2);
+                    session.apply_change(start_line, 0, end_line, 0, &text);
+                    assert_valid_markers(&session, &baseline);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn forgets_lines_typed_over() {
+        let baseline = "fn main() {\n}\n";
+        let mut session = DiffSession::new(baseline, baseline);
+        let mut typed = String::new();
+        for c in "let value = 12345;".chars().cycle().take(5_000) {
+            let column = typed.len() as u32;
+            session.apply_change(2, column, 2, column, &c.to_string());
+            typed.push(c);
+        }
+        assert_eq!(session.text(), format!("fn main() {{\n{}}}\n", typed));
+        assert!(session.line_ids.len() < 2_000);
+        assert_matches_full_diff(&session, baseline);
+
+        session.apply_change(2, 0, 2, typed.len() as u32, "");
+        assert!(session.diffs().is_empty());
+    }
+}
@@ -0,0 +1,335 @@
+use std::collections::VecDeque;
+
+use serde::Serialize;
+use similar::ChangeTag;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::binary::is_binary;
+use crate::clock::deadline_after;
+use crate::{split_lines, text_changes, DiffOptions, HunkKind};
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen(typescript_custom_section)]
+const SIDE_BY_SIDE_TS: &'static str = r#"
+export type RowKind =
+  | "equal"
+  | "add"
+  | "delete"
+  | "modify"
+  | "movedFrom"
+  | "movedTo"
+  | "collapsed";
+
+export interface SideBySideRow {
+  kind: RowKind;
+  /** 1-based, missing for a gap on the left. */
+  oldLine?: number;
+  /** 1-based, missing for a gap on the right. */
+  newLine?: number;
+  /** How many unchanged rows a collapsed row stands for. */
+  hiddenRows?: number;
+}
+"#;
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "SideBySideRow[]")]
+    pub type SideBySideRowArray;
+}
+
+/// What a `SideBySideRow` shows, matching the markers of `line_diff`.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RowKind {
+    /// Unchanged, or a change the options ignore.
+    Equal,
+    Add,
+    Delete,
+    Modify,
+    MovedFrom,
+    MovedTo,
+    /// A stretch of unchanged rows left out by `side_by_side_rows`.
+    Collapsed,
+}
+
+/// A row of a side-by-side view, with the 1-based line shown on each side or
+/// `None` for a gap.
+///
+/// A collapsed row has the first hidden line of each side.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SideBySideRow {
+    pub kind: RowKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_line: Option<u32>,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub hidden_rows: u32,
+}
+
+fn is_zero(count: &u32) -> bool {
+    *count == 0
+}
+
+/// Aligns the old and new text into rows for a side-by-side view, from the
+/// same diff as `line_diff_with_options` with the same options: an added
+/// line has a gap on the left, a deleted line one on the right and a
+/// modified line is next to the line it replaced.
+///
+/// With `collapse_context`, unchanged stretches keep that many rows next to
+/// changes and the rest becomes a single `Collapsed` row. Binary content has
+/// no rows.
+pub fn side_by_side_rows(
+    old_text: &str,
+    new_text: &str,
+    options: &DiffOptions,
+    collapse_context: Option<u32>,
+) -> Vec<SideBySideRow> {
+    if is_binary(old_text.as_bytes()) || is_binary(new_text.as_bytes()) {
+        return Vec::new();
+    }
+    let old_lines = split_lines(old_text);
+    let new_lines = split_lines(new_text);
+    let rows = aligned_rows(&old_lines, &new_lines, options);
+    match collapse_context {
+        Some(context) => collapse(rows, context as usize),
+        None => rows,
+    }
+}
+
+/// Like `side_by_side_rows`, as `SideBySideRow` objects.
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn side_by_side_row_objects(
+    old_text: &str,
+    new_text: &str,
+    options: &DiffOptions,
+    collapse_context: Option<u32>,
+) -> Result<SideBySideRowArray, JsValue> {
+    let rows = side_by_side_rows(old_text, new_text, options, collapse_context);
+    Ok(serde_wasm_bindgen::to_value(&rows)?.unchecked_into())
+}
+
+// A row for every line, paired up the way `collect_diffs` pairs deletes and
+// inserts into modifies.
+pub(crate) fn aligned_rows(
+    old_lines: &[&str],
+    new_lines: &[&str],
+    options: &DiffOptions,
+) -> Vec<SideBySideRow> {
+    let deadline = deadline_after(options.timeout_ms);
+    let changes = text_changes(old_lines, new_lines, options, deadline, None);
+    let moved = |kind: HunkKind, line: u32| {
+        changes.moves.iter().any(|hunk| {
+            let (start, end) = match kind {
+                HunkKind::MovedFrom => (hunk.old_start_line, hunk.old_end_line),
+                _ => (hunk.start_line, hunk.end_line),
+            };
+            hunk.kind == kind && (start..=end).contains(&line)
+        })
+    };
+    let row = |kind, old_line, new_line| SideBySideRow {
+        kind,
+        old_line,
+        new_line,
+        hidden_rows: 0,
+    };
+
+    let mut rows = Vec::with_capacity(old_lines.len().max(new_lines.len()));
+    // deleted lines not paired with an insert yet
+    let mut pending = VecDeque::new();
+    let (mut old_line, mut new_line) = (1, 1);
+    let mut breaks = changes.breaks.iter().peekable();
+    for (index, &(tag, ignored)) in changes.tags.iter().enumerate() {
+        let at_break = breaks.next_if_eq(&&index).is_some();
+        if at_break || tag == ChangeTag::Equal || (ignored && tag == ChangeTag::Delete) {
+            rows.extend(
+                pending
+                    .drain(..)
+                    .map(|old| row(RowKind::Delete, Some(old), None)),
+            );
+        }
+        match (tag, ignored) {
+            (ChangeTag::Equal, _) => {
+                rows.push(row(RowKind::Equal, Some(old_line), Some(new_line)));
+            }
+            (ChangeTag::Delete, true) => {
+                let kind = if moved(HunkKind::MovedFrom, old_line) {
+                    RowKind::MovedFrom
+                } else {
+                    RowKind::Equal
+                };
+                rows.push(row(kind, Some(old_line), None));
+            }
+            (ChangeTag::Insert, true) => {
+                let kind = if moved(HunkKind::MovedTo, new_line) {
+                    RowKind::MovedTo
+                } else {
+                    RowKind::Equal
+                };
+                rows.push(row(kind, None, Some(new_line)));
+            }
+            (ChangeTag::Delete, false) => pending.push_back(old_line),
+            (ChangeTag::Insert, false) => rows.push(match pending.pop_front() {
+                Some(old) => row(RowKind::Modify, Some(old), Some(new_line)),
+                None => row(RowKind::Add, None, Some(new_line)),
+            }),
+        }
+        if tag != ChangeTag::Insert {
+            old_line += 1;
+        }
+        if tag != ChangeTag::Delete {
+            new_line += 1;
+        }
+    }
+    rows.extend(
+        pending
+            .drain(..)
+            .map(|old| row(RowKind::Delete, Some(old), None)),
+    );
+    rows
+}
+
+// Keeps `context` equal rows next to changes, replacing the others in each
+// stretch with a `Collapsed` row when that hides more than one.
+fn collapse(rows: Vec<SideBySideRow>, context: usize) -> Vec<SideBySideRow> {
+    let mut collapsed = Vec::with_capacity(rows.len());
+    let mut start = 0;
+    while start < rows.len() {
+        let len = rows[start..]
+            .iter()
+            .take_while(|row| row.kind == RowKind::Equal)
+            .count();
+        if len == 0 {
+            collapsed.push(rows[start]);
+            start += 1;
+            continue;
+        }
+        let end = start + len;
+        // no context to keep before the first change or after the last one
+        let keep_before = if start == 0 { 0 } else { context };
+        let keep_after = if end == rows.len() { 0 } else { context };
+        let hidden = len.saturating_sub(keep_before + keep_after);
+        if hidden > 1 {
+            let first = rows[start + keep_before];
+            collapsed.extend_from_slice(&rows[start..start + keep_before]);
+            collapsed.push(SideBySideRow {
+                kind: RowKind::Collapsed,
+                hidden_rows: hidden as u32,
+                ..first
+            });
+            collapsed.extend_from_slice(&rows[end - keep_after..end]);
+        } else {
+            collapsed.extend_from_slice(&rows[start..end]);
+        }
+        start = end;
+    }
+    collapsed
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::side_by_side::{side_by_side_rows, RowKind, SideBySideRow};
+    use crate::{diff_lines, DiffOptions, HunkKind};
+
+    fn row(kind: RowKind, old_line: Option<u32>, new_line: Option<u32>) -> SideBySideRow {
+        SideBySideRow {
+            kind,
+            old_line,
+            new_line,
+            hidden_rows: 0,
+        }
+    }
+
+    #[test]
+    fn aligns_changes() {
+        let old = "a\nb\nc\nd\ne\n";
+        let new = "a\nB\nc\nnew\nd\n";
+        assert_eq!(
+            side_by_side_rows(old, new, &DiffOptions::default(), None),
+            vec![
+                row(RowKind::Equal, Some(1), Some(1)),
+                row(RowKind::Modify, Some(2), Some(2)),
+                row(RowKind::Equal, Some(3), Some(3)),
+                row(RowKind::Add, None, Some(4)),
+                row(RowKind::Equal, Some(4), Some(5)),
+                row(RowKind::Delete, Some(5), None),
+            ]
+        );
+        assert_eq!(
+            side_by_side_rows("a\0", "b\0", &DiffOptions::default(), None),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn rows_agree_with_markers() {
+        let function = "fn moved() {\n    let total = compute();\n    total\n}\n";
+        let old = format!("{}a\nb\nc\nd\ne\nf\ng\nh\nlet x = 1;\n", function);
+        let new = format!("a\nB\nc\nd\nf\ng\nh\nnew\n{}let x = 2;\n", function);
+        let options = DiffOptions {
+            detect_moves: true,
+            similarity_threshold: 0.5,
+            ..DiffOptions::default()
+        };
+        let rows = side_by_side_rows(&old, &new, &options, None);
+        // every new line with a marker has a row of the same kind
+        for hunk in diff_lines(&old, &new, &options) {
+            let kind = match hunk.kind {
+                HunkKind::Add => RowKind::Add,
+                HunkKind::Modify => RowKind::Modify,
+                HunkKind::MovedTo => RowKind::MovedTo,
+                _ => continue,
+            };
+            for line in hunk.start_line..=hunk.end_line {
+                let row = rows.iter().find(|row| row.new_line == Some(line));
+                assert_eq!(row.map(|row| row.kind), Some(kind), "line {}", line);
+            }
+        }
+        let kinds = |kind| rows.iter().filter(|row| row.kind == kind).count();
+        assert_eq!(kinds(RowKind::MovedFrom), 4);
+        assert_eq!(kinds(RowKind::MovedTo), 4);
+        // `b` and `B` are not similar enough to be paired
+        assert_eq!(kinds(RowKind::Delete), 2);
+        assert_eq!(kinds(RowKind::Modify), 1);
+        // both sides have every line once, in order
+        let old_lines: Vec<u32> = rows.iter().filter_map(|row| row.old_line).collect();
+        let new_lines: Vec<u32> = rows.iter().filter_map(|row| row.new_line).collect();
+        assert_eq!(old_lines, (1..=13).collect::<Vec<_>>());
+        assert_eq!(new_lines, (1..=13).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn collapses_unchanged_stretches() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n10\n";
+        let collapsed = |old_line, new_line, hidden_rows| SideBySideRow {
+            kind: RowKind::Collapsed,
+            old_line: Some(old_line),
+            new_line: Some(new_line),
+            hidden_rows,
+        };
+        assert_eq!(
+            side_by_side_rows(old, new, &DiffOptions::default(), Some(1)),
+            vec![
+                collapsed(1, 1, 3),
+                row(RowKind::Equal, Some(4), Some(4)),
+                row(RowKind::Modify, Some(5), Some(5)),
+                row(RowKind::Equal, Some(6), Some(6)),
+                collapsed(7, 7, 4),
+            ]
+        );
+        // a single hidden row is shown instead
+        assert_eq!(
+            side_by_side_rows(old, new, &DiffOptions::default(), Some(3)).len(),
+            9
+        );
+        assert_eq!(
+            side_by_side_rows(old, old, &DiffOptions::default(), Some(3)),
+            vec![collapsed(1, 1, 10)]
+        );
+    }
+}
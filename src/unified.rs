@@ -0,0 +1,195 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::binary::is_binary;
+use crate::hunks::{patch_hunks, LineTag, PatchHunk};
+use crate::lines::split_lines;
+use crate::DiffOptions;
+
+/// Renders a unified diff (`@@ -a,b +c,d @@`) of the two texts with
+/// `context` lines around each change, using the default `DiffOptions`.
+/// Returns an empty string when the texts are equal.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn unified_diff(
+    old_text: &str,
+    new_text: &str,
+    context: u32,
+    old_name: &str,
+    new_name: &str,
+) -> String {
+    unified_diff_with_options(
+        old_text,
+        new_text,
+        context,
+        old_name,
+        new_name,
+        &DiffOptions::default(),
+    )
+}
+
+/// Like `unified_diff`, aligned exactly like `line_diff_with_options` with
+/// the same options.
+///
+/// Changes the options ignore are left out of the patch, like `git diff -w`:
+/// lines that only differ in ignored whitespace are written as context from
+/// the old text, and ignored blank lines are kept as they were in the old
+/// text.
+///
+/// Binary content is not diffed, like git it is reported as
+/// `Binary files <old_name> and <new_name> differ`.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn unified_diff_with_options(
+    old_text: &str,
+    new_text: &str,
+    context: u32,
+    old_name: &str,
+    new_name: &str,
+    options: &DiffOptions,
+) -> String {
+    if is_binary(old_text.as_bytes()) || is_binary(new_text.as_bytes()) {
+        if old_text == new_text {
+            return String::new();
+        }
+        return format!("Binary files {} and {} differ\n", old_name, new_name);
+    }
+
+    let old_lines = split_lines(old_text);
+    let new_lines = split_lines(new_text);
+    let hunks = patch_hunks(&old_lines, &new_lines, context, options);
+    if hunks.is_empty() {
+        return String::new();
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+    for hunk in &hunks {
+        write_hunk(&mut out, hunk, &old_lines, &new_lines);
+    }
+    out
+}
+
+fn write_hunk(out: &mut String, hunk: &PatchHunk, old_lines: &[&str], new_lines: &[&str]) {
+    let (old, patched) = (&hunk.old_range, &hunk.patched_range);
+    out.push_str(&format!(
+        "@@ -{} +{} @@\n",
+        hunk_range(old.start, old.len()),
+        hunk_range(patched.start, patched.len())
+    ));
+    for line in &hunk.lines {
+        // context is written from the old text, ignored inserted lines are
+        // not part of the patch
+        let (kind, line) = match (line.tag, line.old, line.new) {
+            (LineTag::Equal, Some(old), _) => (' ', old_lines[old]),
+            (LineTag::Equal, None, _) => continue,
+            (LineTag::Delete, Some(old), _) => ('-', old_lines[old]),
+            (_, _, Some(new)) => ('+', new_lines[new]),
+            _ => unreachable!("patch lines are in the text they come from"),
+        };
+        out.push(kind);
+        out.push_str(line);
+        if !line.ends_with(['\r', '\n']) {
+            out.push_str("\n\\ No newline at end of file\n");
+        }
+    }
+}
+
+// `start` is 0-based. An empty range names the line before it, so it is the
+// same number as the 1-based start of the following line minus one.
+fn hunk_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::patch::apply_patch;
+    use crate::unified::{unified_diff, unified_diff_with_options};
+    use crate::DiffOptions;
+
+    // The patch has to apply cleanly: no offsets and no fuzz.
+    fn apply(old_text: &str, patch: &str) -> String {
+        let result = apply_patch(old_text, patch);
+        for i in 0..result.hunk_count() {
+            let hunk = result.hunk(i).unwrap();
+            assert!(hunk.applied && hunk.offset == 0 && hunk.fuzz == 0);
+        }
+        result.text()
+    }
+
+    #[test]
+    fn no_changes() {
+        assert_eq!(unified_diff("a\nb\n", "a\nb\n", 3, "a", "b"), "");
+    }
+
+    #[test]
+    fn single_hunk() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n";
+        let new = "1\n2\n3\nfour\n5\n6\n7\n8\n";
+        let out = unified_diff(old, new, 2, "a/file.txt", "b/file.txt");
+        assert_eq!(
+            out,
+            "--- a/file.txt\n+++ b/file.txt\n@@ -2,5 +2,5 @@\n 2\n 3\n-4\n+four\n 5\n 6\n"
+        );
+        assert_eq!(apply(old, &out), new);
+    }
+
+    #[test]
+    fn separate_and_merged_hunks() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+        let new = "one\n2\n3\n4\n5\n6\n7\n8\n9\n10\n12\n13\n";
+        let out = unified_diff(old, new, 1, "old", "new");
+        assert_eq!(
+            out,
+            "--- old\n+++ new\n@@ -1,2 +1,2 @@\n-1\n+one\n 2\n@@ -10,3 +10,3 @@\n 10\n-11\n 12\n+13\n"
+        );
+        assert_eq!(apply(old, &out), new);
+
+        // with more context both changes end up in one hunk
+        let out = unified_diff(old, new, 5, "old", "new");
+        assert_eq!(out.matches("@@ -").count(), 1);
+        assert_eq!(apply(old, &out), new);
+    }
+
+    #[test]
+    fn insert_into_empty_file() {
+        let out = unified_diff("", "a\nb\n", 3, "old", "new");
+        assert_eq!(out, "--- old\n+++ new\n@@ -0,0 +1,2 @@\n+a\n+b\n");
+        assert_eq!(apply("", &out), "a\nb\n");
+    }
+
+    #[test]
+    fn missing_newline_at_end() {
+        let old = "a\nb";
+        let new = "a\nb\n";
+        let out = unified_diff(old, new, 3, "old", "new");
+        assert_eq!(
+            out,
+            "--- old\n+++ new\n@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+b\n"
+        );
+        assert_eq!(apply(old, &out), new);
+        assert_eq!(apply(new, &unified_diff(new, old, 3, "new", "old")), old);
+    }
+
+    #[test]
+    fn binary_files() {
+        let out = unified_diff("a\0b", "a\0c", 3, "a/logo.png", "b/logo.png");
+        assert_eq!(out, "Binary files a/logo.png and b/logo.png differ\n");
+        assert_eq!(unified_diff("a\0b", "a\0b", 3, "old", "new"), "");
+    }
+
+    #[test]
+    fn ignored_whitespace_stays_old() {
+        let old = "a\nb \n\nc\n";
+        let new = "a\nb\nc\nd\n";
+        let options = DiffOptions {
+            ignore_trailing_whitespace: true,
+            ignore_blank_lines: true,
+            ..DiffOptions::default()
+        };
+        let out = unified_diff_with_options(old, new, 3, "old", "new", &options);
+        assert_eq!(out, "--- old\n+++ new\n@@ -2,3 +2,4 @@\n b \n \n c\n+d\n");
+        assert_eq!(apply(old, &out), "a\nb \n\nc\nd\n");
+    }
+}